@@ -1,21 +1,153 @@
 ///自定义错误类型，为方便处理各个模块的各种错误，将他专门拿出来做一个模块
 pub type ZxResult<T> = Result<T, ZxError>;
-#[allow(non_camel_case_types, dead_code)]
-#[repr(i32)]
-#[derive(Debug, Clone, Copy)]
-#[derive(PartialEq)]   //为了方便断言错误返回
-pub enum ZxError {
-    OK = 0,
-    /// 一个不指向handle的特定的handle value
-    BAD_HANDLE = -11,
-    /// 操作主体对于执行这个操作来说是错误的类型
-    /// 例如： 尝试执行 message_read 在 thread handle.
-    WRONG_TYPE = -12,
-    // 权限检查错误
-    // 调用者没有执行该操作的权限
-    ACCESS_DENIED = -30,
-    //对端已经关闭
-    PEER_CLOSED=-13,
-    //需要等待
-    SHOULD_WAIT=-14,
+
+/// 由一张 (名字 = 数值, 说明) 的表同时生成错误枚举、名字和说明，保证三者不会对不上。
+/// 表的第一行是表示成功的 OK，它的名字是 `ZX_OK`，其余各行的名字都带 `ZX_ERR_` 前缀。
+macro_rules! define_errors {
+    (
+        $ok:ident = $ok_value:expr, $ok_desc:expr;
+        $($name:ident = $value:expr, $desc:expr;)*
+    ) => {
+        #[allow(non_camel_case_types, dead_code)]
+        #[repr(i32)]
+        #[derive(Debug, Clone, Copy)]
+        #[derive(PartialEq, Eq)]   //为了方便断言错误返回
+        pub enum ZxError {
+            #[doc = $ok_desc]
+            $ok = $ok_value,
+            $(
+                #[doc = $desc]
+                $name = $value,
+            )*
+        }
+
+        impl ZxError {
+            /// Zircon 中的状态名，例如 `ZX_ERR_NOT_FOUND`
+            pub fn name(self) -> &'static str {
+                match self {
+                    ZxError::$ok => concat!("ZX_", stringify!($ok)),
+                    $(ZxError::$name => concat!("ZX_ERR_", stringify!($name)),)*
+                }
+            }
+            /// 一句话的说明
+            pub fn description(self) -> &'static str {
+                match self {
+                    ZxError::$ok => $ok_desc,
+                    $(ZxError::$name => $desc,)*
+                }
+            }
+        }
+
+        impl TryFrom<i32> for ZxError {
+            /// 不认识的状态值原样返回
+            type Error = i32;
+            fn try_from(value: i32) -> Result<Self, i32> {
+                match value {
+                    x if x == $ok_value => Ok(ZxError::$ok),
+                    $(x if x == $value => Ok(ZxError::$name),)*
+                    _ => Err(value),
+                }
+            }
+        }
+    };
+}
+
+define_errors! {
+    OK = 0, "成功";
+    INTERNAL = -1, "内核内部出现了意料之外的错误";
+    NOT_SUPPORTED = -2, "对象不支持这个操作";
+    NO_RESOURCES = -3, "资源不足，例如地址空间中找不到足够大的空位";
+    NO_MEMORY = -4, "内存不足";
+    INTERNAL_INTR_RETRY = -6, "系统调用被打断，需要重试";
+    INVALID_ARGS = -10, "参数不合法";
+    BAD_HANDLE = -11, "句柄值没有指向任何句柄";
+    WRONG_TYPE = -12, "操作主体对于执行这个操作来说是错误的类型，例如对线程句柄执行 message_read";
+    BAD_SYSCALL = -13, "系统调用号不存在";
+    OUT_OF_RANGE = -14, "参数超出了合法范围，例如读写的偏移超过了 VMO 的大小";
+    BUFFER_TOO_SMALL = -15, "调用者提供的缓冲区太小";
+    BAD_STATE = -20, "操作对象当前所处的状态不允许执行这个操作，例如对一个已经启动的线程再次 start";
+    TIMED_OUT = -21, "等待的截止时间已经过去";
+    SHOULD_WAIT = -22, "暂时无法完成，需要等待";
+    CANCELED = -23, "操作被取消，例如线程在等待期间被杀死";
+    PEER_CLOSED = -24, "对端已经关闭";
+    NOT_FOUND = -25, "要找的对象不存在";
+    ALREADY_EXISTS = -26, "要创建的对象已经存在";
+    ALREADY_BOUND = -27, "要绑定的对象已经被绑定";
+    UNAVAILABLE = -28, "资源暂时不可用";
+    ACCESS_DENIED = -30, "调用者没有执行该操作的权限";
+    IO = -40, "输入输出错误";
+    IO_REFUSED = -41, "设备拒绝了请求";
+    IO_DATA_INTEGRITY = -42, "数据校验失败";
+    IO_DATA_LOSS = -43, "数据丢失";
+    IO_NOT_PRESENT = -44, "设备不存在";
+    IO_OVERRUN = -45, "缓冲区溢出";
+    IO_MISSED_DEADLINE = -46, "没有在截止时间之前完成";
+    IO_INVALID = -47, "设备返回的数据不合法";
+    BAD_PATH = -50, "路径不合法";
+    NOT_DIR = -51, "不是目录";
+    NOT_FILE = -52, "不是普通文件";
+    FILE_BIG = -53, "文件太大";
+    NO_SPACE = -54, "存储空间不足";
+    NOT_EMPTY = -55, "目录不为空";
+    STOP = -60, "迭代提前结束";
+    NEXT = -61, "继续下一个";
+    ASYNC = -62, "操作将异步完成";
+    PROTOCOL_NOT_SUPPORTED = -70, "不支持的协议";
+    ADDRESS_UNREACHABLE = -71, "地址不可达";
+    ADDRESS_IN_USE = -72, "地址已被占用";
+    NOT_CONNECTED = -73, "没有连接";
+    CONNECTION_REFUSED = -74, "连接被拒绝";
+    CONNECTION_RESET = -75, "连接被重置";
+    CONNECTION_ABORTED = -76, "连接被中止";
+    INTERNAL_INTR_KILLED = -502, "线程在系统调用中被杀死";
+}
+
+impl From<ZxError> for i32 {
+    fn from(err: ZxError) -> i32 {
+        err as i32
+    }
+}
+
+impl core::fmt::Display for ZxError {
+    /// 例如 `ZX_ERR_NOT_FOUND (-25): 要找的对象不存在`
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} ({}): {}", self.name(), *self as i32, self.description())
+    }
+}
+
+/// `core::error::Error` 在 no_std 下也可用，这样 std 构建中 ZxError 就能和 `?`、`Box<dyn Error>` 配合使用
+impl core::error::Error for ZxError {}
+
+#[cfg(test)]
+mod error_test {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn round_trip() {
+        for value in -600..=0 {
+            if let Ok(err) = ZxError::try_from(value) {
+                assert_eq!(i32::from(err), value);
+            }
+        }
+        assert_eq!(ZxError::try_from(-25), Ok(ZxError::NOT_FOUND));
+        assert_eq!(ZxError::try_from(-502), Ok(ZxError::INTERNAL_INTR_KILLED));
+        assert_eq!(ZxError::try_from(-5), Err(-5));
+        assert_eq!(ZxError::try_from(1), Err(1));
+        assert_eq!(ZxError::try_from(0), Ok(ZxError::OK));
+        let status: i32 = ZxError::SHOULD_WAIT.into();
+        assert_eq!(status, -22);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(ZxError::OK.name(), "ZX_OK");
+        assert_eq!(ZxError::BUFFER_TOO_SMALL.name(), "ZX_ERR_BUFFER_TOO_SMALL");
+        assert_eq!(
+            format!("{}", ZxError::PEER_CLOSED),
+            "ZX_ERR_PEER_CLOSED (-24): 对端已经关闭"
+        );
+        let err: &dyn core::error::Error = &ZxError::TIMED_OUT;
+        assert_eq!(format!("{}", err), "ZX_ERR_TIMED_OUT (-21): 等待的截止时间已经过去");
+    }
 }
@@ -3,3 +3,28 @@ use super::*;
 
 mod channel;
 pub use self::channel::*;
+
+mod port;
+pub use self::port::*;
+
+mod event;
+pub use self::event::*;
+
+mod eventpair;
+pub use self::eventpair::*;
+
+mod socket;
+pub use self::socket::*;
+
+mod fifo;
+pub use self::fifo::*;
+
+use crate::{error::*, object::Signal};
+
+///检查用户要修改的信号，事件和事件对都只允许用户操作 SIGNALED 和用户信号
+fn check_user_signal(signal: Signal) -> ZxResult<()> {
+    if !(Signal::SIGNALED | Signal::USER_ALL).contains(signal) {
+        return Err(ZxError::INVALID_ARGS);
+    }
+    Ok(())
+}
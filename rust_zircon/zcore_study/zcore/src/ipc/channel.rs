@@ -2,11 +2,20 @@ use {
     super::*,
     crate::error::*,
     crate::object::*,
-    alloc::collections::VecDeque,
+    crate::time::Time,
+    alloc::collections::{BTreeMap, VecDeque},
+    core::sync::atomic::{AtomicU32, Ordering},
     alloc::sync::{Arc, Weak},
     spin::Mutex,
     alloc::vec::Vec
 };
+/// 一条消息最多携带的数据字节数
+pub const MAX_MSG_BYTES: usize = 65536;
+/// 一条消息最多携带的句柄数
+pub const MAX_MSG_HANDLES: usize = 64;
+/// 每个端点的接收队列最多积压的消息数，积压满了对端就不可写
+pub const MAX_PENDING_MESSAGES: usize = 3500;
+
 #[derive(Default)]
 pub struct MessagePacket {
     /// message packet携带的数据data
@@ -22,12 +31,26 @@ pub struct Channel {
     peer: Mutex<Weak<Channel>>, 
     ///接收端队列，为什么明明是vecdeque双端动态队列，却只有接受端？这是因为发送端其实就是peer,可以利用其直接将数据写到peer对应的channel的recv中。
     recv_queue: Mutex<VecDeque<TMes>>,   
+    ///正在等待回复的 call，key 是事务号，回复到达后放在 value 中，不进入接收队列
+    call_reply: Mutex<BTreeMap<TxID, Option<TMes>>>,
+    ///用来生成下一个事务号
+    next_txid: AtomicU32,
 }
 
 type TMes = MessagePacket;
 
+/// 事务号，保存在 call 消息数据的前 4 个字节中
+pub type TxID = u32;
+
 //先模拟继承基类
-impl_kobject!(Channel);
+impl_kobject!(Channel, CHANNEL
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_CHANNEL
+    }
+    fn related_koid(&self) -> KoID {
+        self.peer.lock().upgrade().map(|p| p.id()).unwrap_or(0)
+    }
+);
 
 #[allow(dead_code)]
 //再单独实现方法
@@ -40,22 +63,22 @@ impl Channel{
     fn peer_closed(&self) -> bool {
         self.peer.lock().upgrade().is_none()
     }
-    fn related_koid(&self) -> KoID {
-        self.peer.lock().upgrade().map(|p| p.id()).unwrap_or(0)
-    }
-
 
     #[allow(dead_code)]
     pub fn create() -> (Arc<Self>, Arc<Self>) {
         let channel0 = Arc::new(Channel {
-            base: KObjectBase::default(),
+            base: KObjectBase::with_signal(Signal::WRITABLE), //新建的端点都是可写的
             peer: Mutex::new(Weak::default()),
             recv_queue: Default::default(),
+            call_reply: Default::default(),
+            next_txid: AtomicU32::new(0),
         });
         let channel1 = Arc::new(Channel {
-            base: KObjectBase::default(),
+            base: KObjectBase::with_signal(Signal::WRITABLE),
             peer: Mutex::new(Arc::downgrade(&channel0)),
             recv_queue: Default::default(),
+            call_reply: Default::default(),
+            next_txid: AtomicU32::new(0),
         });
         //今天忽然反应过来了，我另一边的channel1获取的是弱引用啊，弱引用又没在引用计数里，为什么不能用get_mut？
         //而且get_mut立刻就使用了获取的可变引用，也不影响引用计数啊，先这么试试。
@@ -69,9 +92,18 @@ impl Channel{
     }
     ///读,成功了返回一个message package也就是TMes。
     pub fn read(&self) -> ZxResult<TMes> {
+        self.read_if(|_| Ok(()))
+    }
+    ///队头的消息先交给 `check` 检查，通过了才取出来；没通过消息留在队列里，返回 `check` 的错误，调用者可以稍后重试
+    pub(crate) fn read_if(&self, check: impl FnOnce(&TMes) -> ZxResult<()>) -> ZxResult<TMes> {
         let mut recv_queue = self.recv_queue.lock();
-        if let Some(_msg) = recv_queue.front() {
+        if let Some(msg) = recv_queue.front() {
+            check(msg)?;
             let msg = recv_queue.pop_front().unwrap();
+            drop(recv_queue);
+            //队列可能读空了，也可能从满变为不满，对端又可以写了
+            let peer = self.peer.lock().upgrade();
+            Self::update_signals(peer.as_deref(), self);
             return Ok(msg);
         }
         if self.peer_closed() {
@@ -80,16 +112,123 @@ impl Channel{
             Err(ZxError::SHOULD_WAIT)
         }
     }
+    ///阻塞地读，队列为空时一直等到有消息、对端关闭或者到达截止时间 `deadline`，超时返回 TIMED_OUT
+    pub fn blocking_read(self: &Arc<Self>, deadline: Time) -> ZxResult<TMes> {
+        let object: Arc<dyn KernelObject> = self.clone();
+        loop {
+            match self.read() {
+                Err(ZxError::SHOULD_WAIT) => {
+                    object_wait_one(&object, Signal::READABLE | Signal::PEER_CLOSED, deadline)?;
+                }
+                result => return result,
+            }
+        }
+    }
     ///写,成功了返回一个空元组，将消息压入对端channel的队尾。
+    ///数据或句柄超过上限返回 OUT_OF_RANGE，对端的接收队列满了返回 SHOULD_WAIT。
     pub fn write(&self, msg: TMes) -> ZxResult<()>{                     //注意，返回元组也是返回！也得用ZxResult处理一下。
+        if msg.data.len() > MAX_MSG_BYTES || msg.handles.len() > MAX_MSG_HANDLES {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
         let peer = self.peer.lock().upgrade().ok_or(ZxError::PEER_CLOSED)?; //先利用peer获取一下对端的channel
-        peer.push_general(msg); 
-        Ok(())
+        peer.push_general(msg, self)
+    }
+    ///发送请求并等待回复：在消息数据的前 4 个字节写入新的事务号，
+    ///直到对端写回一条事务号相同的消息，这条回复不会出现在接收队列中，其他消息留在队列里。
+    ///消息不足 4 个字节返回 INVALID_ARGS，对端关闭返回 PEER_CLOSED，到达 `deadline` 返回 TIMED_OUT。
+    pub fn call(&self, mut msg: TMes, deadline: Time) -> ZxResult<TMes> {
+        if msg.data.len() < core::mem::size_of::<TxID>() {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let txid = self.new_txid();
+        msg.data[..4].copy_from_slice(&txid.to_ne_bytes());
+        self.call_reply.lock().insert(txid, None);
+        if let Err(err) = self.write(msg) {
+            self.call_reply.lock().remove(&txid);
+            return Err(err);
+        }
+        loop {
+            if let Some(reply) = self.take_reply(txid) {
+                return Ok(reply);
+            }
+            let err = if self.peer_closed() {
+                ZxError::PEER_CLOSED
+            } else if deadline.is_expired() {
+                ZxError::TIMED_OUT
+            } else {
+                core::hint::spin_loop();
+                continue;
+            };
+            //放弃等待之前再看一眼，回复可能恰好在对端关闭之前到达
+            let mut call_reply = self.call_reply.lock();
+            return call_reply.remove(&txid).flatten().ok_or(err);
+        }
+    }
+    ///生成一个事务号。内核生成的事务号最高位总是 1，和用户自己选的事务号区分开
+    fn new_txid(&self) -> TxID {
+        0x8000_0000 | self.next_txid.fetch_add(1, Ordering::SeqCst)
+    }
+    ///取走事务号为 `txid` 的回复
+    fn take_reply(&self, txid: TxID) -> Option<TMes> {
+        let mut call_reply = self.call_reply.lock();
+        let reply = call_reply.get_mut(&txid)?.take()?;
+        call_reply.remove(&txid);
+        Some(reply)
     }
-    ///将消息包压入队尾
-fn push_general(&self, msg: TMes) {  
+    ///将消息包压入队尾，如果是某个 call 在等的回复，就直接交给它。
+    ///`writer` 是写入这条消息的对端，队列满了就清除它的 WRITABLE。
+fn push_general(&self, msg: TMes, writer: &Channel) -> ZxResult<()> {  
+        if msg.data.len() >= core::mem::size_of::<TxID>() {
+            let txid = TxID::from_ne_bytes(msg.data[..4].try_into().unwrap());
+            if let Some(slot @ None) = self.call_reply.lock().get_mut(&txid) {
+                *slot = Some(msg);
+                return Ok(());
+            }
+        }
         let mut send_queue = self.recv_queue.lock();
+        if send_queue.len() >= MAX_PENDING_MESSAGES {
+            return Err(ZxError::SHOULD_WAIT);
+        }
         send_queue.push_back(msg); 
+        drop(send_queue);
+        Self::update_signals(Some(writer), self);
+        Ok(())
+    }
+    ///`writer` 向 `reader` 写入或者 `reader` 读走消息之后，按 `reader` 接收队列的状态重新计算
+    ///`reader` 的 READABLE 和 `writer` 的 WRITABLE，`writer` 为 None 表示对端已经关闭。
+    ///
+    ///信号会调用回调，回调里可能再读写这个通道，所以和 socket 一样，放掉队列的锁之后才修改信号，
+    ///修改完再看一次队列，期间被别人读写过就重来。
+    fn update_signals(writer: Option<&Channel>, reader: &Channel) {
+        loop {
+            let state = reader.queue_signals();
+            let (readable, writable) = state;
+            reader.base.signal_change(Signal::READABLE, readable);
+            if let Some(writer) = writer {
+                writer.base.signal_change(Signal::WRITABLE, writable);
+            }
+            if reader.queue_signals() == state {
+                return;
+            }
+        }
+    }
+    ///按这个端点接收队列的状态，这一端应有的 READABLE 和对端（写端）应有的 WRITABLE
+    fn queue_signals(&self) -> (Signal, Signal) {
+        let len = self.recv_queue.lock().len();
+        let mut readable = Signal::empty();
+        let mut writable = Signal::empty();
+        readable.set(Signal::READABLE, len > 0);
+        writable.set(Signal::WRITABLE, len < MAX_PENDING_MESSAGES);
+        (readable, writable)
+    }
+}
+
+impl Drop for Channel {
+    ///端点销毁时通知对端：对端不再可写，并且对端已关闭
+    fn drop(&mut self) {
+        if let Some(peer) = self.peer.lock().upgrade() {
+            peer.base.signal_change(Signal::WRITABLE, Signal::PEER_CLOSED);
+        }
     }
 }
 
@@ -140,4 +279,147 @@ mod channel_test{
         assert_eq!(channel0.read().err(), Some(ZxError::SHOULD_WAIT));
         assert_eq!(channel1.read().err(), Some(ZxError::SHOULD_WAIT));
     }
-}
\ No newline at end of file
+    #[test]
+    fn signal() {
+        let (channel0, channel1) = Channel::create();
+        assert_eq!(channel0.signal(), Signal::WRITABLE);
+        assert_eq!(channel1.signal(), Signal::WRITABLE);
+
+        //有消息到达时可读，读空后不再可读
+        channel0.write(MessagePacket::default()).unwrap();
+        assert_eq!(channel1.signal(), Signal::WRITABLE | Signal::READABLE);
+        channel0.write(MessagePacket::default()).unwrap();
+        channel1.read().unwrap();
+        assert_eq!(channel1.signal(), Signal::WRITABLE | Signal::READABLE);
+        channel1.read().unwrap();
+        assert_eq!(channel1.signal(), Signal::WRITABLE);
+
+        //对端关闭
+        drop(channel0);
+        assert_eq!(channel1.signal(), Signal::PEER_CLOSED);
+        assert_eq!(channel1.read().err(), Some(ZxError::PEER_CLOSED));
+    }
+    #[test]
+    fn callback_reads_channel() {
+        use alloc::boxed::Box;
+        let (channel0, channel1) = Channel::create();
+        //回调在通道变得可读时就把消息读走，修改信号时如果还拿着队列的锁就会死锁
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let reader = Arc::downgrade(&channel1);
+        let received1 = received.clone();
+        channel1.add_signal_callback(Box::new(move |signal| {
+            if signal.contains(Signal::READABLE) {
+                if let Some(reader) = reader.upgrade() {
+                    while let Ok(msg) = reader.read() {
+                        received1.lock().push(msg.data);
+                    }
+                }
+            }
+            false
+        }));
+        channel0.write(MessagePacket { data: Vec::from("hello"), handles: Vec::new() }).unwrap();
+        channel0.write(MessagePacket { data: Vec::from("world"), handles: Vec::new() }).unwrap();
+        assert_eq!(*received.lock(), [Vec::from("hello"), Vec::from("world")]);
+        assert_eq!(channel1.signal(), Signal::WRITABLE);
+    }
+    #[test]
+    fn wait_readable() {
+        use crate::time;
+        use std::thread;
+        let (channel0, channel1) = Channel::create();
+        let object: Arc<dyn KernelObject> = channel1.clone();
+        let reader = thread::spawn(move || {
+            object_wait_one(&object, Signal::READABLE | Signal::PEER_CLOSED, time::INFINITE)
+        });
+        channel0.write(MessagePacket::default()).unwrap();
+        let signal = reader.join().unwrap().unwrap();
+        assert!(signal.contains(Signal::READABLE));
+        assert!(channel1.read().is_ok());
+    }
+    #[test]
+    fn blocking_read() {
+        use crate::time::{self, Duration};
+        use std::thread;
+        let (channel0, channel1) = Channel::create();
+        assert_eq!(
+            channel1.blocking_read(time::INFINITE_PAST).err(),
+            Some(ZxError::TIMED_OUT)
+        );
+        //等到消息到来
+        let channel = channel1.clone();
+        let reader = thread::spawn(move || channel.blocking_read(time::INFINITE));
+        channel0.write(MessagePacket { data: alloc::vec![1, 2], handles: Vec::new() }).unwrap();
+        assert_eq!(reader.join().unwrap().unwrap().data, [1, 2]);
+        //等到截止时间
+        let clock = time::CLOCK_TEST_LOCK.lock();
+        let channel = channel1.clone();
+        let deadline = Time::after(Duration::from_millis(1));
+        let reader = thread::spawn(move || channel.blocking_read(deadline));
+        time::advance(Duration::from_millis(1));
+        assert_eq!(reader.join().unwrap().err(), Some(ZxError::TIMED_OUT));
+        drop(clock);
+        //等到对端关闭
+        let reader = thread::spawn(move || channel1.blocking_read(time::INFINITE));
+        drop(channel0);
+        assert_eq!(reader.join().unwrap().err(), Some(ZxError::PEER_CLOSED));
+    }
+    #[test]
+    fn call() {
+        use crate::time;
+        use std::thread;
+        let (client, server) = Channel::create();
+        assert_eq!(
+            client.call(MessagePacket::default(), time::INFINITE).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        let request = || MessagePacket { data: alloc::vec![0, 0, 0, 0, 1], handles: Vec::new() };
+        //服务端先写一条无关的消息，再回复
+        let handler = thread::spawn(move || {
+            let mut msg = server.blocking_read(time::INFINITE).unwrap();
+            assert_eq!(msg.data[4], 1);
+            server.write(MessagePacket { data: alloc::vec![9; 4], handles: Vec::new() }).unwrap();
+            msg.data[4] = 2;
+            server.write(msg).unwrap();
+            server
+        });
+        let reply = client.call(request(), time::INFINITE).unwrap();
+        assert_eq!(reply.data[4], 2);
+        assert_ne!(reply.data[..4], [0; 4]);
+        //无关的消息留在接收队列中
+        assert_eq!(client.read().unwrap().data, [9; 4]);
+        assert_eq!(client.read().err(), Some(ZxError::SHOULD_WAIT));
+
+        let server = handler.join().unwrap();
+        assert_eq!(client.call(request(), time::INFINITE_PAST).err(), Some(ZxError::TIMED_OUT));
+        //超时之后迟到的回复进入普通的接收队列
+        server.write(server.read().unwrap()).unwrap();
+        assert!(client.read().is_ok());
+        drop(server);
+        assert_eq!(client.call(request(), time::INFINITE).err(), Some(ZxError::PEER_CLOSED));
+    }
+    #[test]
+    fn limits() {
+        use alloc::vec;
+        let (channel0, channel1) = Channel::create();
+        let msg = |len, handles| MessagePacket {
+            data: vec![0; len],
+            handles: (0..handles)
+                .map(|_| Handle::new(DummyObject::new(), Rights::DUPLICATE))
+                .collect(),
+        };
+        assert_eq!(channel0.write(msg(MAX_MSG_BYTES + 1, 0)).err(), Some(ZxError::OUT_OF_RANGE));
+        assert_eq!(channel0.write(msg(0, MAX_MSG_HANDLES + 1)).err(), Some(ZxError::OUT_OF_RANGE));
+        channel0.write(msg(MAX_MSG_BYTES, MAX_MSG_HANDLES)).unwrap();
+
+        //写满对端的接收队列
+        for _ in 1..MAX_PENDING_MESSAGES {
+            channel0.write(msg(1, 0)).unwrap();
+        }
+        assert!(!channel0.signal().contains(Signal::WRITABLE));
+        assert_eq!(channel0.write(msg(1, 0)).err(), Some(ZxError::SHOULD_WAIT));
+        //对端读走一条之后又可写了
+        assert_eq!(channel1.read().unwrap().data.len(), MAX_MSG_BYTES);
+        assert!(channel0.signal().contains(Signal::WRITABLE));
+        channel0.write(msg(1, 0)).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::object::*,
+    alloc::sync::Arc,
+};
+
+///事件对象，它不携带任何数据，只有信号，是最简单的同步原语。
+pub struct Event {
+    base: KObjectBase,
+}
+
+impl_kobject!(Event, EVENT
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_EVENT
+    }
+);
+
+impl Event {
+    ///创建一个新事件
+    pub fn new() -> Arc<Self> {
+        Arc::new(Event {
+            base: KObjectBase::default(),
+        })
+    }
+    ///用户修改事件的信号：先清除 `clear` 再置位 `set`，只能操作 SIGNALED 和用户信号，
+    ///取名 user_signal 是为了和读取信号的 `KernelObject::signal` 区分开
+    pub fn user_signal(&self, clear: Signal, set: Signal) -> ZxResult<()> {
+        check_user_signal(clear | set)?;
+        self.base.signal_change(clear, set);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod event_test {
+    use super::*;
+    use crate::time;
+
+    #[test]
+    fn signal() {
+        let event = Event::new();
+        assert_eq!(event.type_name(), "Event");
+        assert_eq!(event.obj_type(), ObjectType::EVENT);
+        assert_eq!(u32::from(event.obj_type()), 5);
+        assert_eq!(event.signal(), Signal::empty());
+
+        event.user_signal(Signal::empty(), Signal::SIGNALED | Signal::USER_SIGNAL_0).unwrap();
+        assert_eq!(event.signal(), Signal::SIGNALED | Signal::USER_SIGNAL_0);
+        event.user_signal(Signal::USER_SIGNAL_0, Signal::empty()).unwrap();
+        assert_eq!(event.signal(), Signal::SIGNALED);
+
+        // 系统信号不能由用户修改
+        assert_eq!(
+            event.user_signal(Signal::empty(), Signal::READABLE).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        assert_eq!(event.signal(), Signal::SIGNALED);
+    }
+
+    #[test]
+    fn wait() {
+        use std::thread;
+        let event = Event::new();
+        let object: Arc<dyn KernelObject> = event.clone();
+        let waiter = thread::spawn(move || object_wait_one(&object, Signal::SIGNALED, time::INFINITE));
+        event.user_signal(Signal::empty(), Signal::SIGNALED).unwrap();
+        assert_eq!(waiter.join().unwrap(), Ok(Signal::SIGNALED));
+    }
+}
@@ -0,0 +1,99 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::object::*,
+    alloc::sync::{Arc, Weak},
+    spin::Mutex,
+};
+
+///事件对，两个互相关联的事件端点，和 channel 一样两端互相持有对方的弱引用。
+///每一端既可以修改自己的信号，也可以修改对端的信号；一端销毁时对端会收到 PEER_CLOSED。
+pub struct EventPair {
+    base: KObjectBase,
+    peer: Mutex<Weak<EventPair>>,
+}
+
+impl_kobject!(EventPair, EVENTPAIR
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_EVENTPAIR
+    }
+    fn related_koid(&self) -> KoID {
+        self.peer.lock().upgrade().map(|p| p.id()).unwrap_or(0)
+    }
+);
+
+impl EventPair {
+    ///创建一对事件端点
+    pub fn create() -> (Arc<Self>, Arc<Self>) {
+        let event0 = Arc::new(EventPair {
+            base: KObjectBase::default(),
+            peer: Mutex::new(Weak::default()),
+        });
+        let event1 = Arc::new(EventPair {
+            base: KObjectBase::default(),
+            peer: Mutex::new(Arc::downgrade(&event0)),
+        });
+        *event0.peer.lock() = Arc::downgrade(&event1);
+        (event0, event1)
+    }
+    ///获取对端，对端已经销毁时返回 PEER_CLOSED
+    pub fn peer(&self) -> ZxResult<Arc<Self>> {
+        self.peer.lock().upgrade().ok_or(ZxError::PEER_CLOSED)
+    }
+    ///用户修改自己的信号，只能操作 SIGNALED 和用户信号
+    pub fn user_signal(&self, clear: Signal, set: Signal) -> ZxResult<()> {
+        check_user_signal(clear | set)?;
+        self.base.signal_change(clear, set);
+        Ok(())
+    }
+    ///用户修改对端的信号，只能操作 SIGNALED 和用户信号，对端已经销毁时返回 PEER_CLOSED
+    pub fn signal_peer(&self, clear: Signal, set: Signal) -> ZxResult<()> {
+        check_user_signal(clear | set)?;
+        self.peer()?.base.signal_change(clear, set);
+        Ok(())
+    }
+}
+
+impl Drop for EventPair {
+    ///端点销毁时通知对端
+    fn drop(&mut self) {
+        if let Some(peer) = self.peer.lock().upgrade() {
+            peer.base.signal_set(Signal::PEER_CLOSED);
+        }
+    }
+}
+
+#[cfg(test)]
+mod eventpair_test {
+    use super::*;
+
+    #[test]
+    fn signal_peer() {
+        let (event0, event1) = EventPair::create();
+        assert!(Arc::ptr_eq(&event0.peer().unwrap(), &event1));
+        assert!(Arc::ptr_eq(&event1.peer().unwrap(), &event0));
+
+        event0.signal_peer(Signal::empty(), Signal::USER_SIGNAL_1).unwrap();
+        assert_eq!(event0.signal(), Signal::empty());
+        assert_eq!(event1.signal(), Signal::USER_SIGNAL_1);
+        event1.user_signal(Signal::USER_SIGNAL_1, Signal::SIGNALED).unwrap();
+        assert_eq!(event1.signal(), Signal::SIGNALED);
+        assert_eq!(
+            event1.signal_peer(Signal::empty(), Signal::PEER_CLOSED).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+    }
+
+    #[test]
+    fn peer_closed() {
+        let (event0, event1) = EventPair::create();
+        event0.user_signal(Signal::empty(), Signal::USER_SIGNAL_0).unwrap();
+        drop(event1);
+        assert_eq!(event0.signal(), Signal::USER_SIGNAL_0 | Signal::PEER_CLOSED);
+        assert_eq!(event0.peer().err(), Some(ZxError::PEER_CLOSED));
+        assert_eq!(
+            event0.signal_peer(Signal::empty(), Signal::USER_SIGNAL_0).err(),
+            Some(ZxError::PEER_CLOSED)
+        );
+    }
+}
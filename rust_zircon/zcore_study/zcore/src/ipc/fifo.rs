@@ -0,0 +1,236 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::object::*,
+    alloc::collections::VecDeque,
+    alloc::sync::{Arc, Weak},
+    spin::Mutex,
+};
+
+/// FIFO 中所有元素的总字节数上限
+pub const FIFO_MAX_BYTES: usize = 4096;
+
+///FIFO 结构体，一对互相持有弱引用的端点，交换固定大小的记录。
+///元素大小和容量在创建时确定，适合用来实现块设备那样的请求环。
+pub struct Fifo {
+    base: KObjectBase,
+    peer: Mutex<Weak<Fifo>>,
+    elem_size: usize,                //每个元素的字节数
+    elem_count: usize,               //接收队列最多能容纳的元素个数
+    recv_queue: Mutex<VecDeque<u8>>, //接收队列，按字节存放，长度总是 elem_size 的整数倍
+}
+
+impl_kobject!(Fifo, FIFO
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_FIFO
+    }
+    fn related_koid(&self) -> KoID {
+        self.peer.lock().upgrade().map(|p| p.id()).unwrap_or(0)
+    }
+);
+
+impl Fifo {
+    /// 创建一对 FIFO 端点，每个端点最多容纳 `elem_count` 个大小为 `elem_size` 的元素。
+    /// 两者都不能为 0，总大小不能超过 FIFO_MAX_BYTES。
+    pub fn create(elem_count: usize, elem_size: usize) -> ZxResult<(Arc<Self>, Arc<Self>)> {
+        if elem_count == 0 || elem_size == 0 {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
+        match elem_count.checked_mul(elem_size) {
+            Some(size) if size <= FIFO_MAX_BYTES => {}
+            _ => return Err(ZxError::OUT_OF_RANGE),
+        }
+        let new = |peer| Fifo {
+            base: KObjectBase::with_signal(Signal::WRITABLE),
+            peer: Mutex::new(peer),
+            elem_size,
+            elem_count,
+            recv_queue: Default::default(),
+        };
+        let fifo0 = Arc::new(new(Weak::default()));
+        let fifo1 = Arc::new(new(Arc::downgrade(&fifo0)));
+        *fifo0.peer.lock() = Arc::downgrade(&fifo1);
+        Ok((fifo0, fifo1))
+    }
+    /// 每个元素的字节数
+    pub fn elem_size(&self) -> usize {
+        self.elem_size
+    }
+    /// 每个端点的元素容量
+    pub fn elem_count(&self) -> usize {
+        self.elem_count
+    }
+    /// 把 `data` 中的若干个元素写到对端，返回实际写入的元素个数。
+    /// `data` 的长度必须是元素大小的非零整数倍；对端快满时只写入放得下的部分，完全满了返回 SHOULD_WAIT。
+    pub fn write(&self, data: &[u8]) -> ZxResult<usize> {
+        if data.is_empty() || !data.len().is_multiple_of(self.elem_size) {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
+        let peer = self.peer.lock().upgrade().ok_or(ZxError::PEER_CLOSED)?;
+        let count = {
+            let mut queue = peer.recv_queue.lock();
+            let free = self.elem_count - queue.len() / self.elem_size;
+            if free == 0 {
+                return Err(ZxError::SHOULD_WAIT);
+            }
+            let count = (data.len() / self.elem_size).min(free);
+            queue.extend(&data[..count * self.elem_size]);
+            count
+        };
+        Self::update_signals(Some(self), &peer);
+        Ok(count)
+    }
+    /// 读取若干个元素填到 `buf` 中，返回实际读到的元素个数。
+    /// `buf` 的长度必须是元素大小的非零整数倍。
+    pub fn read(&self, buf: &mut [u8]) -> ZxResult<usize> {
+        if buf.is_empty() || !buf.len().is_multiple_of(self.elem_size) {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
+        let count = {
+            let mut queue = self.recv_queue.lock();
+            if queue.is_empty() {
+                return Err(if self.peer.lock().upgrade().is_none() {
+                    ZxError::PEER_CLOSED
+                } else {
+                    ZxError::SHOULD_WAIT
+                });
+            }
+            let n = buf.len().min(queue.len());
+            for (dst, src) in buf.iter_mut().zip(queue.drain(..n)) {
+                *dst = src;
+            }
+            n / self.elem_size
+        };
+        //读走元素之后队列有了空间，对端又可以写了
+        let peer = self.peer.lock().upgrade();
+        Self::update_signals(peer.as_deref(), self);
+        Ok(count)
+    }
+    /// `writer` 向 `reader` 写入或者 `reader` 读走元素之后，按 `reader` 接收队列的状态重新计算
+    /// `reader` 的 READABLE 和 `writer` 的 WRITABLE，`writer` 为 None 表示对端已经关闭。
+    /// 和 socket 一样，信号在放掉队列的锁之后修改，改完再看一次队列，期间被别人读写过就重来。
+    fn update_signals(writer: Option<&Fifo>, reader: &Fifo) {
+        loop {
+            let state = reader.queue_signals();
+            let (readable, writable) = state;
+            reader.base.signal_change(Signal::READABLE, readable);
+            if let Some(writer) = writer {
+                writer.base.signal_change(Signal::WRITABLE, writable);
+            }
+            if reader.queue_signals() == state {
+                return;
+            }
+        }
+    }
+    /// 按这个端点接收队列的状态，这一端应有的 READABLE 和对端应有的 WRITABLE
+    fn queue_signals(&self) -> (Signal, Signal) {
+        let len = self.recv_queue.lock().len();
+        let mut readable = Signal::empty();
+        let mut writable = Signal::empty();
+        readable.set(Signal::READABLE, len > 0);
+        writable.set(Signal::WRITABLE, len / self.elem_size < self.elem_count);
+        (readable, writable)
+    }
+}
+
+impl Drop for Fifo {
+    ///端点销毁时通知对端
+    fn drop(&mut self) {
+        if let Some(peer) = self.peer.lock().upgrade() {
+            peer.base.signal_change(Signal::WRITABLE, Signal::PEER_CLOSED);
+        }
+    }
+}
+
+#[cfg(test)]
+mod fifo_test {
+    use super::*;
+
+    #[test]
+    fn create() {
+        let (fifo0, _fifo1) = Fifo::create(4, 8).unwrap();
+        assert_eq!(fifo0.elem_count(), 4);
+        assert_eq!(fifo0.elem_size(), 8);
+        assert_eq!(fifo0.signal(), Signal::WRITABLE);
+        assert_eq!(Fifo::create(0, 8).err(), Some(ZxError::OUT_OF_RANGE));
+        assert_eq!(Fifo::create(4, 0).err(), Some(ZxError::OUT_OF_RANGE));
+        assert_eq!(Fifo::create(4096, 2).err(), Some(ZxError::OUT_OF_RANGE));
+        assert_eq!(Fifo::create(usize::MAX, 2).err(), Some(ZxError::OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn read_write() {
+        let (fifo0, fifo1) = Fifo::create(3, 2).unwrap();
+        assert_eq!(fifo0.write(&[1, 2, 3, 4]), Ok(2));
+        assert_eq!(fifo0.write(&[1, 2, 3]).err(), Some(ZxError::OUT_OF_RANGE));
+        assert_eq!(fifo1.signal(), Signal::WRITABLE | Signal::READABLE);
+
+        // 对端快满了，只写进去一个
+        assert_eq!(fifo0.write(&[5, 6, 7, 8]), Ok(1));
+        assert!(!fifo0.signal().contains(Signal::WRITABLE));
+        assert_eq!(fifo0.write(&[9, 9]).err(), Some(ZxError::SHOULD_WAIT));
+
+        // 批量读
+        let mut buf = [0u8; 4];
+        assert_eq!(fifo1.read(&mut buf), Ok(2));
+        assert_eq!(buf, [1, 2, 3, 4]);
+        assert!(fifo0.signal().contains(Signal::WRITABLE));
+        assert_eq!(fifo1.read(&mut buf), Ok(1));
+        assert_eq!(buf[..2], [5, 6]);
+        assert_eq!(fifo1.signal(), Signal::WRITABLE);
+        assert_eq!(fifo1.read(&mut buf).err(), Some(ZxError::SHOULD_WAIT));
+        assert_eq!(fifo1.read(&mut buf[..1]).err(), Some(ZxError::OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn concurrent_read_write() {
+        use crate::time;
+        use std::thread;
+        // 写端写满就等 WRITABLE，读端读空就等 READABLE，丢了任何一次唤醒都会卡住
+        const COUNT: usize = 64;
+        const TOTAL: usize = 4 * COUNT;
+        let (fifo0, fifo1) = Fifo::create(COUNT, 2).unwrap();
+        let writer = thread::spawn(move || {
+            let object: Arc<dyn KernelObject> = fifo0.clone();
+            let data = [1u8; 20];
+            let mut sent = 0;
+            while sent < TOTAL {
+                match fifo0.write(&data[..2 * (TOTAL - sent).min(10)]) {
+                    Ok(n) => sent += n,
+                    Err(ZxError::SHOULD_WAIT) => {
+                        object_wait_one(&object, Signal::WRITABLE, time::INFINITE).unwrap();
+                    }
+                    Err(err) => panic!("unexpected error {:?}", err),
+                }
+            }
+            fifo0
+        });
+        let object: Arc<dyn KernelObject> = fifo1.clone();
+        let mut buf = [0u8; 14];
+        let mut received = 0;
+        while received < TOTAL {
+            match fifo1.read(&mut buf) {
+                Ok(n) => received += n,
+                Err(ZxError::SHOULD_WAIT) => {
+                    object_wait_one(&object, Signal::READABLE, time::INFINITE).unwrap();
+                }
+                Err(err) => panic!("unexpected error {:?}", err),
+            }
+        }
+        let fifo0 = writer.join().unwrap();
+        assert_eq!(fifo0.signal(), Signal::WRITABLE);
+        assert_eq!(fifo1.signal(), Signal::WRITABLE);
+    }
+
+    #[test]
+    fn peer_closed() {
+        let (fifo0, fifo1) = Fifo::create(2, 1).unwrap();
+        fifo1.write(&[1]).unwrap();
+        drop(fifo1);
+        assert_eq!(fifo0.signal(), Signal::READABLE | Signal::PEER_CLOSED);
+        assert_eq!(fifo0.write(&[1]).err(), Some(ZxError::PEER_CLOSED));
+        let mut buf = [0u8; 2];
+        assert_eq!(fifo0.read(&mut buf), Ok(1));
+        assert_eq!(fifo0.read(&mut buf).err(), Some(ZxError::PEER_CLOSED));
+    }
+}
@@ -0,0 +1,302 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::object::*,
+    crate::time::Time,
+    alloc::boxed::Box,
+    alloc::collections::VecDeque,
+    alloc::sync::{Arc, Weak},
+    alloc::vec::Vec,
+    spin::Mutex,
+};
+
+///端口对象，是一个数据包队列。
+///其他对象的信号变化可以异步地投递到端口上，用户也可以直接往端口里放数据包，
+///这样一个线程只要等待一个端口，就能同时服务很多个对象。
+pub struct Port {
+    base: KObjectBase,
+    queue: Mutex<VecDeque<PortPacket>>, //待取走的数据包
+    waits: Mutex<AsyncWaits>,           //还没有投递的异步等待
+}
+
+#[derive(Default)]
+struct AsyncWaits {
+    next_seq: u64,
+    waits: Vec<AsyncWait>,
+}
+
+///一次 `wait_async` 注册，投递或者取消之后被移除
+struct AsyncWait {
+    seq: u64,                          //端口内部的编号，回调投递之后用它找到自己
+    object: Weak<dyn KernelObject>,    //被观察的对象
+    key: u64,
+    callback: Option<SignalCallbackId>, //注册在对象上的回调，注册完成之前为 None
+}
+
+impl_kobject!(Port, PORT
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_PORT
+    }
+);
+
+///端口中的数据包
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortPacket {
+    ///用户注册时给出的 key，用来区分数据包的来源
+    pub key: u64,
+    ///数据包的内容
+    pub data: PacketData,
+}
+
+///数据包的内容，按类型区分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketData {
+    ///用户通过 `queue` 放入的数据包
+    User([u8; 32]),
+    ///对象信号变化产生的数据包
+    Signal(PacketSignal),
+}
+
+///信号数据包
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketSignal {
+    ///注册时等待的信号
+    pub trigger: Signal,
+    ///投递时对象上的信号
+    pub observed: Signal,
+}
+
+impl Port {
+    ///创建一个空端口
+    pub fn new() -> Arc<Self> {
+        Arc::new(Port {
+            base: KObjectBase::default(),
+            queue: Default::default(),
+            waits: Default::default(),
+        })
+    }
+    ///放入一个用户数据包
+    pub fn queue(&self, key: u64, data: [u8; 32]) {
+        self.push(PortPacket {
+            key,
+            data: PacketData::User(data),
+        });
+    }
+    ///把数据包放到队尾，端口变得可读
+    fn push(&self, packet: PortPacket) {
+        self.queue.lock().push_back(packet);
+        self.update_signal();
+    }
+    ///按队列是否为空更新 READABLE。
+    ///信号在放掉队列的锁之后才修改，这样端口上的回调（例如端口等待自己）可以往队列里投递而不会死锁；
+    ///修改之后再看一次队列，期间被别人改变了就重来，保证最后留下的信号和队列一致
+    fn update_signal(&self) {
+        loop {
+            let readable = !self.queue.lock().is_empty();
+            if readable {
+                self.base.signal_set(Signal::READABLE);
+            } else {
+                self.base.signal_clear(Signal::READABLE);
+            }
+            if readable != self.queue.lock().is_empty() {
+                return;
+            }
+        }
+    }
+    ///取出队头的数据包，队列为空时一直等到截止时间 `deadline`，超时返回 TIMED_OUT
+    pub fn wait(self: &Arc<Self>, deadline: Time) -> ZxResult<PortPacket> {
+        let object: Arc<dyn KernelObject> = self.clone();
+        loop {
+            let packet = self.queue.lock().pop_front();
+            if let Some(packet) = packet {
+                self.update_signal();
+                return Ok(packet);
+            }
+            //可能有多个等待者，被唤醒后数据包可能已经被别人取走，所以要回到循环开头重新取
+            object_wait_one(&object, Signal::READABLE, deadline)?;
+        }
+    }
+    ///异步等待：当 `object` 上 `signal` 中的任意信号被置位时，向这个端口投递一个信号数据包。
+    ///注册是一次性的，投递之后就失效。可以用 `cancel` 取消，端口被销毁时也会从对象上注销。
+    pub fn wait_async(self: &Arc<Self>, object: &Arc<dyn KernelObject>, key: u64, signal: Signal) {
+        let seq = {
+            let mut waits = self.waits.lock();
+            let seq = waits.next_seq;
+            waits.next_seq += 1;
+            waits.waits.push(AsyncWait {
+                seq,
+                object: Arc::downgrade(object),
+                key,
+                callback: None,
+            });
+            seq
+        };
+        let port = Arc::downgrade(self); //用弱引用，不让被观察的对象延长端口的生命周期
+        let id = object.add_signal_callback(Box::new(move |observed| {
+            let port = match port.upgrade() {
+                Some(port) => port,
+                None => return true,
+            };
+            if !observed.intersects(signal) {
+                return false;
+            }
+            port.waits.lock().waits.retain(|w| w.seq != seq);
+            port.push(PortPacket {
+                key,
+                data: PacketData::Signal(PacketSignal {
+                    trigger: signal,
+                    observed,
+                }),
+            });
+            true
+        }));
+        //注册时信号可能已经满足，回调已经投递并移除了这一项，那就什么也不用记
+        if let Some(wait) = self.waits.lock().waits.iter_mut().find(|w| w.seq == seq) {
+            wait.callback = Some(id);
+        }
+    }
+    ///取消在 `object` 上用 `key` 注册的所有还没有投递的异步等待，已经投递的数据包不受影响。
+    ///没有这样的注册时返回 NOT_FOUND。
+    pub fn cancel(&self, object: &Arc<dyn KernelObject>, key: u64) -> ZxResult<()> {
+        let id = object.id();
+        let cancelled: Vec<AsyncWait> = {
+            let mut waits = self.waits.lock();
+            let (cancelled, rest) = core::mem::take(&mut waits.waits).into_iter().partition(|w| {
+                w.key == key && w.object.upgrade().is_some_and(|o| o.id() == id)
+            });
+            waits.waits = rest;
+            cancelled
+        };
+        if cancelled.is_empty() {
+            return Err(ZxError::NOT_FOUND);
+        }
+        Self::unregister(cancelled);
+        Ok(())
+    }
+    ///从被观察的对象上注销回调，回调可能会运行，所以不能拿着端口的锁调用
+    fn unregister(waits: Vec<AsyncWait>) {
+        for wait in waits {
+            if let (Some(object), Some(id)) = (wait.object.upgrade(), wait.callback) {
+                object.remove_signal_callback(id);
+            }
+        }
+    }
+}
+
+impl Drop for Port {
+    ///端口销毁时注销所有还没有投递的异步等待，不让回调一直留在长期存在的对象上
+    fn drop(&mut self) {
+        let waits = core::mem::take(&mut self.waits.get_mut().waits);
+        Self::unregister(waits);
+    }
+}
+
+#[cfg(test)]
+mod port_test {
+    use super::*;
+    use crate::time;
+
+    #[test]
+    fn user_packet() {
+        let port = Port::new();
+        assert_eq!(port.wait(time::INFINITE_PAST).err(), Some(ZxError::TIMED_OUT));
+        port.queue(1, [1; 32]);
+        port.queue(2, [2; 32]);
+        assert!(port.signal().contains(Signal::READABLE));
+        assert_eq!(
+            port.wait(time::INFINITE_PAST),
+            Ok(PortPacket {
+                key: 1,
+                data: PacketData::User([1; 32]),
+            })
+        );
+        assert_eq!(port.wait(time::INFINITE_PAST).unwrap().key, 2);
+        assert!(!port.signal().contains(Signal::READABLE));
+    }
+
+    #[test]
+    fn wait_async() {
+        let port = Port::new();
+        let (channel0, channel1) = Channel::create();
+        let (channel2, channel3) = Channel::create();
+        let object1: Arc<dyn KernelObject> = channel1.clone();
+        let object3: Arc<dyn KernelObject> = channel3.clone();
+        port.wait_async(&object1, 1, Signal::READABLE);
+        port.wait_async(&object3, 3, Signal::READABLE | Signal::PEER_CLOSED);
+        assert_eq!(port.wait(time::INFINITE_PAST).err(), Some(ZxError::TIMED_OUT));
+
+        channel0.write(MessagePacket::default()).unwrap();
+        assert_eq!(
+            port.wait(time::INFINITE),
+            Ok(PortPacket {
+                key: 1,
+                data: PacketData::Signal(PacketSignal {
+                    trigger: Signal::READABLE,
+                    observed: Signal::READABLE | Signal::WRITABLE,
+                }),
+            })
+        );
+        // 注册是一次性的，再有消息也不会投递
+        channel0.write(MessagePacket::default()).unwrap();
+        assert_eq!(port.wait(time::INFINITE_PAST).err(), Some(ZxError::TIMED_OUT));
+
+        drop(channel2);
+        let packet = port.wait(time::INFINITE).unwrap();
+        assert_eq!(packet.key, 3);
+        match packet.data {
+            PacketData::Signal(signal) => assert_eq!(signal.observed, Signal::PEER_CLOSED),
+            _ => panic!("expect a signal packet"),
+        }
+    }
+
+    #[test]
+    fn cancel() {
+        let port = Port::new();
+        let event = Event::new();
+        let object: Arc<dyn KernelObject> = event.clone();
+        port.wait_async(&object, 1, Signal::SIGNALED);
+        port.wait_async(&object, 2, Signal::SIGNALED);
+        //回调持有端口的弱引用，取消之后回调被注销，弱引用也就没了
+        assert_eq!(Arc::weak_count(&port), 2);
+        port.cancel(&object, 1).unwrap();
+        assert_eq!(Arc::weak_count(&port), 1);
+        assert_eq!(port.cancel(&object, 1).err(), Some(ZxError::NOT_FOUND));
+
+        event.signal_set(Signal::SIGNALED);
+        assert_eq!(port.wait(time::INFINITE_PAST).unwrap().key, 2);
+        assert_eq!(port.wait(time::INFINITE_PAST).err(), Some(ZxError::TIMED_OUT));
+        //已经投递过的注册也不能再取消
+        assert_eq!(port.cancel(&object, 2).err(), Some(ZxError::NOT_FOUND));
+        assert_eq!(Arc::weak_count(&port), 0);
+
+        //端口销毁时注销还在等待的回调
+        let port = Port::new();
+        let (channel0, channel1) = Channel::create();
+        let object: Arc<dyn KernelObject> = channel1;
+        port.wait_async(&object, 3, Signal::READABLE);
+        drop(port);
+        channel0.write(MessagePacket::default()).unwrap();
+    }
+
+    #[test]
+    fn wait_async_self() {
+        let port = Port::new();
+        let object: Arc<dyn KernelObject> = port.clone();
+        // 端口等待自己变得可读，回调里往自己的队列投递，不会死锁
+        port.wait_async(&object, 1, Signal::READABLE);
+        port.queue(2, [0; 32]);
+        assert_eq!(port.wait(time::INFINITE_PAST).unwrap().key, 2);
+        assert_eq!(port.wait(time::INFINITE_PAST).unwrap().key, 1);
+        assert!(!port.signal().contains(Signal::READABLE));
+    }
+
+    #[test]
+    fn wait_from_other_thread() {
+        use std::thread;
+        let port = Port::new();
+        let port1 = port.clone();
+        let waiter = thread::spawn(move || port1.wait(time::INFINITE));
+        port.queue(7, [0; 32]);
+        assert_eq!(waiter.join().unwrap().unwrap().key, 7);
+    }
+}
@@ -0,0 +1,440 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::object::*,
+    alloc::collections::VecDeque,
+    alloc::sync::{Arc, Weak},
+    alloc::vec::Vec,
+    bitflags::bitflags,
+    spin::Mutex,
+};
+
+/// 每个端点接收缓冲区的容量（字节）
+pub const SOCKET_CAPACITY: usize = 256 * 1024;
+
+///socket 结构体，和 channel 一样是一对互相持有弱引用的端点，但传递的是字节而不是消息。
+///流模式下数据没有边界，写入时缓冲区放不下就只写一部分；数据报模式下每次写入是一个完整的数据报。
+///每个端点的接收缓冲区都有容量上限，满了之后写端会得到 SHOULD_WAIT。
+pub struct Socket {
+    base: KObjectBase,
+    peer: Mutex<Weak<Socket>>,
+    mode: SocketMode,
+    inner: Mutex<SocketInner>, //这个端点的接收缓冲区，写端直接往对端的这里写
+}
+
+impl_kobject!(Socket, SOCKET
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_SOCKET
+    }
+    fn related_koid(&self) -> KoID {
+        self.peer.lock().upgrade().map(|p| p.id()).unwrap_or(0)
+    }
+);
+
+#[derive(Default)]
+struct SocketInner {
+    data: VecDeque<u8>,               //流模式下缓冲的字节
+    datagrams: VecDeque<Vec<u8>>,     //数据报模式下缓冲的数据报
+    buffered: usize,                  //缓冲区中的总字节数
+    read_disabled: bool,              //对端不会再写入数据（对端关闭了写，或者自己关闭了读）
+    rx_threshold: usize,              //读阈值，缓冲的数据不少于它时置位 READ_THRESHOLD，0 表示不启用
+    tx_threshold: usize,              //写阈值，对端剩余空间不少于它时置位 WRITE_THRESHOLD，0 表示不启用
+}
+
+/// socket 的工作模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketMode {
+    /// 字节流
+    Stream,
+    /// 数据报，保留消息边界
+    Datagram,
+}
+
+bitflags! {
+    /// `shutdown` 要关闭的方向
+    pub struct SocketShutdown: u32 {
+        /// 关闭读：对端不能再写过来
+        const READ = 1 << 0;
+        /// 关闭写：自己不能再写出去
+        const WRITE = 1 << 1;
+    }
+}
+
+impl Socket {
+    /// 创建一对 socket 端点
+    pub fn create(mode: SocketMode) -> (Arc<Self>, Arc<Self>) {
+        let socket0 = Arc::new(Socket {
+            base: KObjectBase::with_signal(Signal::WRITABLE),
+            peer: Mutex::new(Weak::default()),
+            mode,
+            inner: Default::default(),
+        });
+        let socket1 = Arc::new(Socket {
+            base: KObjectBase::with_signal(Signal::WRITABLE),
+            peer: Mutex::new(Arc::downgrade(&socket0)),
+            mode,
+            inner: Default::default(),
+        });
+        *socket0.peer.lock() = Arc::downgrade(&socket1);
+        (socket0, socket1)
+    }
+    /// 获取工作模式
+    pub fn mode(&self) -> SocketMode {
+        self.mode
+    }
+    fn peer(&self) -> ZxResult<Arc<Self>> {
+        self.peer.lock().upgrade().ok_or(ZxError::PEER_CLOSED)
+    }
+    /// 向对端写入数据，返回实际写入的字节数。
+    /// 流模式下缓冲区剩余空间不够时只写入一部分；数据报模式下要么整个写入，要么返回 SHOULD_WAIT。
+    pub fn write(&self, data: &[u8]) -> ZxResult<usize> {
+        let peer = self.peer()?;
+        let written = {
+            let mut inner = peer.inner.lock();
+            if inner.read_disabled {
+                return Err(ZxError::BAD_STATE);
+            }
+            let free = SOCKET_CAPACITY - inner.buffered;
+            let written = match self.mode {
+                SocketMode::Stream => {
+                    if data.is_empty() {
+                        return Ok(0);
+                    }
+                    if free == 0 {
+                        return Err(ZxError::SHOULD_WAIT);
+                    }
+                    let n = data.len().min(free);
+                    inner.data.extend(&data[..n]);
+                    n
+                }
+                SocketMode::Datagram => {
+                    if data.is_empty() {
+                        return Err(ZxError::INVALID_ARGS);
+                    }
+                    if data.len() > SOCKET_CAPACITY {
+                        return Err(ZxError::OUT_OF_RANGE);
+                    }
+                    if data.len() > free {
+                        return Err(ZxError::SHOULD_WAIT);
+                    }
+                    inner.datagrams.push_back(Vec::from(data));
+                    data.len()
+                }
+            };
+            inner.buffered += written;
+            written
+        };
+        Self::update_signals(Some(self), &peer);
+        Ok(written)
+    }
+    /// 读取数据到 `buf`，返回读到的字节数。
+    /// 数据报模式下每次读一个数据报，`buf` 放不下的部分被丢弃。
+    pub fn read(&self, buf: &mut [u8]) -> ZxResult<usize> {
+        self.read_inner(buf, false)
+    }
+    /// 和 `read` 一样，但是不把数据从缓冲区中取走
+    pub fn peek(&self, buf: &mut [u8]) -> ZxResult<usize> {
+        self.read_inner(buf, true)
+    }
+    fn read_inner(&self, buf: &mut [u8], peek: bool) -> ZxResult<usize> {
+        let read = {
+            let mut inner = self.inner.lock();
+            if inner.buffered == 0 {
+                return Err(if inner.read_disabled {
+                    ZxError::BAD_STATE
+                } else if self.peer.lock().upgrade().is_none() {
+                    ZxError::PEER_CLOSED
+                } else {
+                    ZxError::SHOULD_WAIT
+                });
+            }
+            let (copied, consumed) = match self.mode {
+                SocketMode::Stream => {
+                    let n = buf.len().min(inner.buffered);
+                    for (dst, src) in buf.iter_mut().zip(inner.data.iter()) {
+                        *dst = *src;
+                    }
+                    (n, n)
+                }
+                SocketMode::Datagram => {
+                    let datagram = inner.datagrams.front().unwrap();
+                    let n = buf.len().min(datagram.len());
+                    buf[..n].copy_from_slice(&datagram[..n]);
+                    (n, datagram.len())
+                }
+            };
+            if peek {
+                return Ok(copied);
+            }
+            match self.mode {
+                SocketMode::Stream => drop(inner.data.drain(..consumed)),
+                SocketMode::Datagram => drop(inner.datagrams.pop_front()),
+            }
+            inner.buffered -= consumed;
+            copied
+        };
+        //读走数据之后缓冲区有了空间，对端又可以写了
+        let peer = self.peer().ok();
+        Self::update_signals(peer.as_deref(), self);
+        Ok(read)
+    }
+    /// 半关闭：关闭读或者写的方向。
+    /// 关闭写之后自己不能再写，对端读完缓冲区里的数据后得到 BAD_STATE；关闭读则反过来。
+    pub fn shutdown(&self, how: SocketShutdown) -> ZxResult<()> {
+        let peer = self.peer()?;
+        if how.contains(SocketShutdown::WRITE) {
+            Self::disable_write(self, &peer);
+        }
+        if how.contains(SocketShutdown::READ) {
+            Self::disable_write(&peer, self);
+        }
+        Ok(())
+    }
+    /// 读阈值，0 表示不启用
+    pub fn rx_threshold(&self) -> usize {
+        self.inner.lock().rx_threshold
+    }
+    /// 设置读阈值：缓冲的数据不少于 `threshold` 字节时置位 READ_THRESHOLD，超过容量返回 INVALID_ARGS
+    pub(crate) fn set_rx_threshold(&self, threshold: usize) -> ZxResult<()> {
+        if threshold > SOCKET_CAPACITY {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        self.inner.lock().rx_threshold = threshold;
+        let peer = self.peer().ok();
+        Self::update_signals(peer.as_deref(), self);
+        Ok(())
+    }
+    /// 写阈值，0 表示不启用
+    pub fn tx_threshold(&self) -> usize {
+        self.inner.lock().tx_threshold
+    }
+    /// 设置写阈值：对端剩余空间不少于 `threshold` 字节时置位 WRITE_THRESHOLD，
+    /// 超过容量返回 INVALID_ARGS，对端已经关闭返回 PEER_CLOSED
+    pub(crate) fn set_tx_threshold(&self, threshold: usize) -> ZxResult<()> {
+        if threshold > SOCKET_CAPACITY {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let peer = self.peer()?;
+        self.inner.lock().tx_threshold = threshold;
+        Self::update_signals(Some(self), &peer);
+        Ok(())
+    }
+    /// `writer` 向 `reader` 写入或者 `reader` 读走数据之后，按 `reader` 缓冲区的状态重新计算
+    /// `reader` 的 READABLE、READ_THRESHOLD 和 `writer` 的 WRITABLE、WRITE_THRESHOLD，
+    /// `writer` 为 None 表示对端已经关闭。
+    ///
+    /// 信号在放掉缓冲区的锁之后才修改，修改完再看一次缓冲区，期间被别人读写过就重来。
+    /// 这样最后一次修改总是和缓冲区一致：不会出现读端刚置位 WRITABLE、写端又用过时的结果把它清掉的情况。
+    fn update_signals(writer: Option<&Socket>, reader: &Socket) {
+        const READER: Signal = Signal::READABLE.union(Signal::READ_THRESHOLD);
+        const WRITER: Signal = Signal::WRITABLE.union(Signal::WRITE_THRESHOLD);
+        loop {
+            let state = reader.buffer_signals(writer);
+            let (reader_signal, writer_signal) = state;
+            reader.base.signal_change(READER, reader_signal);
+            if let Some(writer) = writer {
+                writer.base.signal_change(WRITER, writer_signal);
+            }
+            if reader.buffer_signals(writer) == state {
+                return;
+            }
+        }
+    }
+    /// 按这个端点接收缓冲区的状态，这一端应有的 READABLE、READ_THRESHOLD
+    /// 和对端（写端 `writer`）应有的 WRITABLE、WRITE_THRESHOLD
+    fn buffer_signals(&self, writer: Option<&Socket>) -> (Signal, Signal) {
+        let (buffered, read_disabled, rx_threshold) = {
+            let inner = self.inner.lock();
+            (inner.buffered, inner.read_disabled, inner.rx_threshold)
+        };
+        //写阈值在写端自己的 inner 里，两把锁不同时拿
+        let tx_threshold = writer.map_or(0, |w| w.inner.lock().tx_threshold);
+        let free = SOCKET_CAPACITY - buffered;
+        let mut reader_signal = Signal::empty();
+        let mut writer_signal = Signal::empty();
+        reader_signal.set(Signal::READABLE, buffered > 0);
+        reader_signal.set(Signal::READ_THRESHOLD, rx_threshold != 0 && buffered >= rx_threshold);
+        writer_signal.set(Signal::WRITABLE, !read_disabled && free > 0);
+        writer_signal.set(Signal::WRITE_THRESHOLD, tx_threshold != 0 && free >= tx_threshold);
+        (reader_signal, writer_signal)
+    }
+    /// 禁止 `writer` 向 `reader` 写入
+    fn disable_write(writer: &Socket, reader: &Socket) {
+        reader.inner.lock().read_disabled = true;
+        reader.base.signal_set(Signal::PEER_WRITE_DISABLED);
+        writer.base.signal_change(Signal::WRITABLE, Signal::WRITE_DISABLED);
+    }
+}
+
+impl Drop for Socket {
+    ///端点销毁时通知对端
+    fn drop(&mut self) {
+        if let Some(peer) = self.peer.lock().upgrade() {
+            peer.base.signal_change(Signal::WRITABLE, Signal::PEER_CLOSED);
+        }
+    }
+}
+
+#[cfg(test)]
+mod socket_test {
+    use super::*;
+
+    #[test]
+    fn stream() {
+        let (socket0, socket1) = Socket::create(SocketMode::Stream);
+        assert_eq!(socket0.mode(), SocketMode::Stream);
+        assert_eq!(socket0.write(b"hello"), Ok(5));
+        assert_eq!(socket0.write(b" world"), Ok(6));
+        assert_eq!(socket1.signal(), Signal::WRITABLE | Signal::READABLE);
+
+        // 没有消息边界
+        let mut buf = [0u8; 8];
+        assert_eq!(socket1.peek(&mut buf), Ok(8));
+        assert_eq!(&buf, b"hello wo");
+        assert_eq!(socket1.read(&mut buf), Ok(8));
+        assert_eq!(&buf, b"hello wo");
+        assert_eq!(socket1.read(&mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"rld");
+        assert_eq!(socket1.signal(), Signal::WRITABLE);
+        assert_eq!(socket1.read(&mut buf).err(), Some(ZxError::SHOULD_WAIT));
+    }
+
+    #[test]
+    fn datagram() {
+        let (socket0, socket1) = Socket::create(SocketMode::Datagram);
+        socket0.write(b"hello").unwrap();
+        socket0.write(b"world!").unwrap();
+        assert_eq!(socket0.write(b"").err(), Some(ZxError::INVALID_ARGS));
+
+        // 保留消息边界，放不下的部分被丢弃
+        let mut buf = [0u8; 4];
+        assert_eq!(socket1.peek(&mut buf), Ok(4));
+        assert_eq!(socket1.read(&mut buf), Ok(4));
+        assert_eq!(&buf, b"hell");
+        let mut buf = [0u8; 16];
+        assert_eq!(socket1.read(&mut buf), Ok(6));
+        assert_eq!(&buf[..6], b"world!");
+    }
+
+    #[test]
+    fn backpressure() {
+        use alloc::vec;
+        let (socket0, socket1) = Socket::create(SocketMode::Stream);
+        let data = vec![1u8; SOCKET_CAPACITY - 10];
+        assert_eq!(socket0.write(&data), Ok(SOCKET_CAPACITY - 10));
+        // 只写得下一部分
+        assert_eq!(socket0.write(&[2u8; 20]), Ok(10));
+        assert!(!socket0.signal().contains(Signal::WRITABLE));
+        assert_eq!(socket0.write(&[3]).err(), Some(ZxError::SHOULD_WAIT));
+
+        // 对端读走数据后又可写了
+        let mut buf = [0u8; 100];
+        socket1.read(&mut buf).unwrap();
+        assert!(socket0.signal().contains(Signal::WRITABLE));
+        assert_eq!(socket0.write(&[3; 200]), Ok(100));
+
+        let (dgram0, _dgram1) = Socket::create(SocketMode::Datagram);
+        assert_eq!(dgram0.write(&vec![0; SOCKET_CAPACITY + 1]).err(), Some(ZxError::OUT_OF_RANGE));
+        dgram0.write(&data).unwrap();
+        assert_eq!(dgram0.write(&[0; 20]).err(), Some(ZxError::SHOULD_WAIT));
+    }
+
+    #[test]
+    fn concurrent_read_write() {
+        use crate::time;
+        use std::thread;
+        // 写端写满就等 WRITABLE，读端读空就等 READABLE，丢了任何一次唤醒都会卡住
+        const TOTAL: usize = 4 * SOCKET_CAPACITY;
+        let (socket0, socket1) = Socket::create(SocketMode::Stream);
+        let writer = thread::spawn(move || {
+            let object: Arc<dyn KernelObject> = socket0.clone();
+            let data = [7u8; 1000];
+            let mut sent = 0;
+            while sent < TOTAL {
+                match socket0.write(&data[..(TOTAL - sent).min(data.len())]) {
+                    Ok(n) => sent += n,
+                    Err(ZxError::SHOULD_WAIT) => {
+                        object_wait_one(&object, Signal::WRITABLE, time::INFINITE).unwrap();
+                    }
+                    Err(err) => panic!("unexpected error {:?}", err),
+                }
+            }
+            socket0
+        });
+        let object: Arc<dyn KernelObject> = socket1.clone();
+        let mut buf = [0u8; 777];
+        let mut received = 0;
+        while received < TOTAL {
+            match socket1.read(&mut buf) {
+                Ok(n) => received += n,
+                Err(ZxError::SHOULD_WAIT) => {
+                    object_wait_one(&object, Signal::READABLE, time::INFINITE).unwrap();
+                }
+                Err(err) => panic!("unexpected error {:?}", err),
+            }
+        }
+        let socket0 = writer.join().unwrap();
+        assert_eq!(socket0.signal(), Signal::WRITABLE);
+        assert_eq!(socket1.signal(), Signal::WRITABLE);
+    }
+
+    #[test]
+    fn shutdown() {
+        let (socket0, socket1) = Socket::create(SocketMode::Stream);
+        socket0.write(b"bye").unwrap();
+        socket0.shutdown(SocketShutdown::WRITE).unwrap();
+        assert_eq!(socket0.signal(), Signal::WRITE_DISABLED);
+        assert!(socket1.signal().contains(Signal::PEER_WRITE_DISABLED));
+        assert_eq!(socket0.write(b"x").err(), Some(ZxError::BAD_STATE));
+
+        // 已经缓冲的数据还能读出来，读完之后是 BAD_STATE
+        let mut buf = [0u8; 8];
+        assert_eq!(socket1.read(&mut buf), Ok(3));
+        assert_eq!(socket1.read(&mut buf).err(), Some(ZxError::BAD_STATE));
+        // 反方向不受影响
+        socket1.write(b"ok").unwrap();
+        assert_eq!(socket0.read(&mut buf), Ok(2));
+
+        // 关闭读，对端就不能再写过来
+        socket0.shutdown(SocketShutdown::READ).unwrap();
+        assert_eq!(socket1.write(b"x").err(), Some(ZxError::BAD_STATE));
+        assert!(socket1.signal().contains(Signal::WRITE_DISABLED));
+    }
+
+    #[test]
+    fn peer_closed() {
+        let (socket0, socket1) = Socket::create(SocketMode::Stream);
+        socket1.write(b"last").unwrap();
+        drop(socket1);
+        assert_eq!(socket0.signal(), Signal::READABLE | Signal::PEER_CLOSED);
+        assert_eq!(socket0.write(b"x").err(), Some(ZxError::PEER_CLOSED));
+        let mut buf = [0u8; 8];
+        assert_eq!(socket0.read(&mut buf), Ok(4));
+        assert_eq!(socket0.read(&mut buf).err(), Some(ZxError::PEER_CLOSED));
+    }
+
+    #[test]
+    fn thresholds() {
+        let (socket0, socket1) = Socket::create(SocketMode::Stream);
+        assert_eq!(socket1.set_rx_threshold(SOCKET_CAPACITY + 1).err(), Some(ZxError::INVALID_ARGS));
+        socket1.set_rx_threshold(4).unwrap();
+        assert_eq!(socket1.rx_threshold(), 4);
+        socket0.write(b"abc").unwrap();
+        assert!(!socket1.signal().contains(Signal::READ_THRESHOLD));
+        socket0.write(b"d").unwrap();
+        assert!(socket1.signal().contains(Signal::READ_THRESHOLD));
+        let mut buf = [0u8; 2];
+        socket1.read(&mut buf).unwrap();
+        assert!(!socket1.signal().contains(Signal::READ_THRESHOLD));
+
+        // 对端剩余空间不少于写阈值
+        socket0.set_tx_threshold(SOCKET_CAPACITY - 2).unwrap();
+        assert!(socket0.signal().contains(Signal::WRITE_THRESHOLD));
+        socket0.write(b"e").unwrap();
+        assert!(!socket0.signal().contains(Signal::WRITE_THRESHOLD));
+        socket1.read(&mut buf).unwrap();
+        assert!(socket0.signal().contains(Signal::WRITE_THRESHOLD));
+        drop(socket1);
+        assert_eq!(socket0.set_tx_threshold(1).err(), Some(ZxError::PEER_CLOSED));
+    }
+}
@@ -3,12 +3,17 @@
            //alloc crate 是 Rust 的一个核心库（core library），它提供了一些基本的内存分配器，允许开发者在不使用标准库的情况下进行内存分配。
            //但不同于其他核心库，alloc是独立于核心库的其他部分编译的，所以在no_std情况下，需要显式的引用它。
 extern crate alloc; //当使用 #![no_std] 时，由于不链接标准库，一些在标准库中定义的全局分配器和内存分配相关的功能将不可用。此时，alloc crate 可以作为一个替代品，提供基本的内存分配功能。
+#[cfg(any(test, feature = "hosted"))]
+extern crate std; //测试和 hosted 构建在宿主机上运行，需要用标准库的线程和时钟
 
 //包含各个模块中的代码
 pub mod object; 
 pub mod task;
 pub mod ipc;
+pub mod vm;
+pub mod user;
 pub mod error;
+pub mod time;
 pub use object::*;
 
 #[cfg(test)]
@@ -23,8 +28,9 @@ mod tests {
         let dummy = DummyObject::new();
         let object: Arc<dyn KernelObject> = dummy;
         assert_eq!(object.type_name(), "DummyObject");
+        assert_eq!(object.obj_type(), ObjectType::NONE);
         assert_eq!(object.name(), "");
-        object.set_name("dummy");
+        object.base().set_name("dummy");
         assert_eq!(object.name(), "dummy");
         assert_eq!(
             format!("{:?}", object),
@@ -17,10 +17,38 @@ pub trait KernelObject: DowncastSync +Debug {  //这里的downcastsync代替了s
     fn id(&self) -> KoID;
     /// 获取对象类型名
     fn type_name(&self) -> &str; //如果返回的字符串是静态的或者生命周期足够长，可以使用 &str。
+    /// 获取对象类型，用于句柄信息和传递句柄时的类型检查
+    fn obj_type(&self) -> ObjectType;
     /// 获取对象名称
     fn name(&self) -> String; //如果需要返回一个动态生成的、可以独立于原始数据存在的字符串副本，或者需要保证字符串的可变性，那么使用 String 更合适。
-    /// 设置对象名称
-    fn set_name(&self, name: &str);
+    /// 获取对象当前的信号
+    fn signal(&self) -> Signal;
+    /// 置位信号
+    fn signal_set(&self, signal: Signal);
+    /// 清除信号
+    fn signal_clear(&self, signal: Signal);
+    /// 先清除 `clear` 中的信号，再置位 `set` 中的信号
+    fn signal_change(&self, clear: Signal, set: Signal);
+    /// 添加一个信号回调，信号发生变化时被调用，回调返回 true 时被移除，返回回调的编号
+    fn add_signal_callback(&self, handler: SignalHandler) -> SignalCallbackId;
+    /// 注销编号为 `id` 的信号回调
+    fn remove_signal_callback(&self, id: SignalCallbackId);
+    /// 新建句柄时默认授予的权限，各类对象在 `impl_kobject!` 中覆盖
+    fn default_rights(&self) -> Rights {
+        Rights::BASIC
+    }
+    /// 与这个对象相关的对象的 ID，例如通道的对端、进程所属的作业，没有时为 0
+    fn related_koid(&self) -> KoID {
+        0
+    }
+    /// 指向这个对象的句柄数
+    fn handle_count(&self) -> u32 {
+        self.handle_counter().get()
+    }
+    /// 对象的句柄计数器，只有 `Handle` 能增减它
+    fn handle_counter(&self) -> &HandleCounter;
+    /// 对象的公共部分。改名等修改操作只在 crate 内可见，外部要经过 `set_property`
+    fn base(&self) -> &KObjectBase;
 }
 impl_downcast!(sync KernelObject); //自动生成kernelobject对应的 向下转换的函数（sync是一个占位符，指示生成的实现是线程安全的）
 /// 对象 ID 类型
@@ -34,4 +62,12 @@ pub use self::handle::*;  //公开引用，使别的模块可以用直接通过o
 
 //创建一个权限子模块
 mod rights;
-pub use self::rights::*;  //比如说这样handle模块就可以直接通过super访问到object从而访问rights模块。
\ No newline at end of file
+pub use self::rights::*;  //比如说这样handle模块就可以直接通过super访问到object从而访问rights模块。
+
+//创建一个信号子模块
+mod signal;
+pub use self::signal::*;
+
+//创建一个对象类型子模块
+mod object_type;
+pub use self::object_type::*;
@@ -5,7 +5,6 @@ use alloc::sync::Arc;
 ///句柄是允许用户程序引用内核对象引用的一种内核结构，它可以被认为是与特定内核对象的会话或连接。
 ///通常情况下，多个进程通过不同的句柄同时访问同一个对象。
 /// 对象可能有多个句柄（在一个或多个进程中）引用它们。但单个句柄只能绑定到单个进程或绑定到内核。
-#[derive(Clone)]
 pub struct Handle {
     pub object: Arc<dyn KernelObject>,
     pub rights: Rights,
@@ -14,8 +13,27 @@ pub struct Handle {
 impl Handle {
     /// 创建一个新句柄
     pub fn new(object: Arc<dyn KernelObject>, rights: Rights) -> Self {
+        object.handle_counter().opened();
         Handle { object, rights }
     }
+    /// 用对象的默认权限创建一个新句柄
+    pub fn with_default_rights(object: Arc<dyn KernelObject>) -> Self {
+        let rights = object.default_rights();
+        Self::new(object, rights)
+    }
+}
+
+impl Clone for Handle {
+    /// 复制出来的句柄也要计入对象的句柄数
+    fn clone(&self) -> Self {
+        Self::new(self.object.clone(), self.rights)
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.object.handle_counter().closed();
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -26,5 +44,12 @@ mod tests {
     fn new_obj_handle() {
         let obj = DummyObject::new();
         let _handle1 = Handle::new(obj.clone(), Rights::BASIC);
+        let handle2 = Handle::with_default_rights(obj.clone());
+        assert_eq!(handle2.rights, Rights::BASIC);
+        let handle3 = handle2.clone();
+        assert_eq!(obj.handle_count(), 3);
+        drop(handle2);
+        drop(handle3);
+        assert_eq!(obj.handle_count(), 1);
     }
 }
\ No newline at end of file
@@ -2,11 +2,16 @@
 use super::*; //为父模块的结构体进行方法实现，引入一个路径，省的在每个需要父类的地方都crate::object::
 use spin::Mutex; //因为不能用依赖操作系统提供系统调用的std,所以用了no_std兼容的spin库中的mutex来实现简单互斥锁。具体实现细节先不深纠，一样用。
 use alloc::sync::Arc;//原子引用计数，用于在多线程环境下安全的共享所有权
+use alloc::vec::Vec;
 use core::sync::atomic::*;
 use super::KernelObject;
+/// 对象名的最大长度，和 Zircon 一样包括结尾的 NUL，所以实际最多保存 31 个字节
+pub const MAX_NAME_LEN: usize = 32;
+
 pub struct KObjectBase {
     //dummy有填充物，哑巴之类的意思，dummyobject就是等待填充啥也干不了的空对象，在实现模拟继承后，由KObjectBase代替
     pub id: KoID,
+    handle_counter: HandleCounter,  //指向这个对象的句柄数，和 id 一样放在锁外面
     inner: Mutex<KObjectBaseInner>, //利用一个带互斥锁的内部可变结构体来存放这个对象可变的成员
 }
 
@@ -21,6 +26,12 @@ pub struct KObjectBase {
 #[derive(Default)] //自动派生deault,以产生默认实例
 struct KObjectBaseInner {
     name: String, //内核对象名
+    signal: Signal, //对象当前的信号
+    signal_callbacks: Vec<(SignalCallbackId, SignalHandler)>, //信号变化时要调用的回调
+    next_callback_id: SignalCallbackId, //下一个回调的编号
+    signal_seq: u64,                    //信号每变化一次就加一
+    dispatching: usize,                 //正在锁外调用回调的线程数
+    cancelled: Vec<SignalCallbackId>,   //在锁外调用期间被注销的回调
 }
 
 impl Default for KObjectBase {
@@ -28,11 +39,31 @@ impl Default for KObjectBase {
     fn default() -> Self {
         KObjectBase {
             id: Self::new_koid(),
+            handle_counter: HandleCounter::default(),
             inner: Default::default(),
         }
     }
 }
 
+/// 对象的句柄计数器。谁都可以读，但只有创建和销毁 `Handle` 时才会增减，
+/// 这样计数总是和真实存在的句柄数一致，不会被外面多减一次减成一个很大的数
+#[derive(Default)]
+pub struct HandleCounter(AtomicU32);
+
+impl HandleCounter {
+    /// 当前的句柄数
+    pub fn get(&self) -> u32 {
+        self.0.load(Ordering::SeqCst)
+    }
+    pub(crate) fn opened(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+    pub(crate) fn closed(&self) {
+        let old = self.0.fetch_sub(1, Ordering::SeqCst);
+        debug_assert!(old > 0, "handle count underflow");
+    }
+}
+
 //id自增
 impl KObjectBase {
     fn new_koid() -> KoID {
@@ -50,21 +81,116 @@ impl KObjectBase {
     pub fn name(&self) -> String {
         self.inner.lock().name.clone() //取得内部互斥锁，访问name,返回一个拷贝。
     }
-    pub fn set_name(&self, name: &str) {
+    /// 设置对象名，超过 MAX_NAME_LEN - 1 字节的部分在 UTF-8 字符边界处被截掉
+    pub(crate) fn set_name(&self, name: &str) {
+        let mut len = name.len().min(MAX_NAME_LEN - 1);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let name = &name[..len];
         //获取空对象的不可变引用（这里是为了保护不可变的部分）
         //可以像访问任何结构体的字段一样直接访问lock函数返回的 MutexGuard<T> 的字段，而不需要先解引用整个 MutexGuard 对象
         self.inner.lock().name = String::from(name); //通过lock()取得内部互斥锁的可变访问权，利用传入的参数修改name。
     }
+    pub fn handle_counter(&self) -> &HandleCounter {
+        &self.handle_counter
+    }
+    /// 创建一个带有初始信号的 KObjectBase
+    pub fn with_signal(signal: Signal) -> Self {
+        let base = KObjectBase::default();
+        base.inner.lock().signal = signal;
+        base
+    }
+    pub fn signal(&self) -> Signal {
+        self.inner.lock().signal
+    }
+    pub fn signal_set(&self, signal: Signal) {
+        self.signal_change(Signal::empty(), signal);
+    }
+    pub fn signal_clear(&self, signal: Signal) {
+        self.signal_change(signal, Signal::empty());
+    }
+    /// 修改信号，信号真的发生变化时依次调用回调，并移除返回 true 的回调。
+    /// 回调在放掉锁之后才调用，回调里再访问这个对象（例如读信号、注册回调）不会死锁。
+    pub fn signal_change(&self, clear: Signal, set: Signal) {
+        let mut inner = self.inner.lock();
+        let old_signal = inner.signal;
+        inner.signal.remove(clear);
+        inner.signal.insert(set);
+        if inner.signal == old_signal {
+            return;
+        }
+        inner.signal_seq += 1;
+        loop {
+            let (signal, seq) = (inner.signal, inner.signal_seq);
+            let mut callbacks = core::mem::take(&mut inner.signal_callbacks);
+            inner.dispatching += 1;
+            drop(inner);
+            callbacks.retain(|(_, f)| !f(signal));
+            inner = self.inner.lock();
+            inner.dispatching -= 1;
+            let cancelled = &inner.cancelled;
+            callbacks.retain(|(id, _)| !cancelled.contains(id));
+            if inner.dispatching == 0 {
+                inner.cancelled.clear();
+            }
+            //调用期间新注册的回调排在后面
+            callbacks.append(&mut inner.signal_callbacks);
+            inner.signal_callbacks = callbacks;
+            //调用期间信号又变了，别的线程看不到被取走的回调，由这里用最新的信号补调一次
+            if inner.signal_seq == seq {
+                return;
+            }
+        }
+    }
+    /// 添加信号回调，添加时先用当前信号调用一次，返回 true 就不再保留。
+    /// 返回的编号可以用来注销回调。
+    pub fn add_signal_callback(&self, handler: SignalHandler) -> SignalCallbackId {
+        let mut inner = self.inner.lock();
+        let id = inner.next_callback_id;
+        inner.next_callback_id += 1;
+        loop {
+            let (signal, seq) = (inner.signal, inner.signal_seq);
+            drop(inner);
+            if handler(signal) {
+                return id;
+            }
+            inner = self.inner.lock();
+            //调用期间信号没有变化才能放心地登记，否则用新的信号再调用一次
+            if inner.signal_seq == seq {
+                inner.signal_callbacks.push((id, handler));
+                return id;
+            }
+        }
+    }
+    /// 注销信号回调，回调已经被移除时什么也不做
+    pub fn remove_signal_callback(&self, id: SignalCallbackId) {
+        let removed = {
+            let mut inner = self.inner.lock();
+            match inner.signal_callbacks.iter().position(|(i, _)| *i == id) {
+                Some(index) => Some(inner.signal_callbacks.remove(index)),
+                None => {
+                    //回调可能正被别的线程拿在锁外调用，记下来，等它放回来时丢掉
+                    if inner.dispatching > 0 {
+                        inner.cancelled.push(id);
+                    }
+                    None
+                }
+            }
+        };
+        drop(removed); //回调持有的引用在锁外释放
+    }
 }
 
 /// 模拟继承！为内核对象 struct 自动实现 `KernelObject` trait 的宏。
 #[macro_export] // 导出宏，可在 crate 外部使用
 macro_rules! impl_kobject { //定义了一个名为 impl_kobject 的宏
-    // 匹配类型名，并可以提供函数覆盖默认实现
+    // 匹配类型名和它的 ObjectType，并可以提供函数覆盖默认实现
     //$class:ident：这部分是宏的一个参数，$class 是参数的名称，而 :ident 表示这个参数应该是一个标识符。
+    // $ty:ident：ObjectType 的变体名，例如 CHANNEL，不对应任何 Zircon 类型的对象写 NONE。
     // $fn:tt：这是宏的另一个参数，$fn 是参数的名称，:tt（token tree）表示这个参数可以是任何有效的 Rust 代码片段。
     //这意味着可以传递一系列 Rust 代码作为参数，这些代码可以是表达式、语句、甚至是模式。
-    ($class:ident $( $fn:tt )*) => { 
+    ($class:ident, $ty:ident $( $fn:tt )*) => { 
 
         // 为对象实现 KernelObject trait，方法直接转发到内部 struct
         impl KernelObject for $class {
@@ -76,13 +202,36 @@ macro_rules! impl_kobject { //定义了一个名为 impl_kobject 的宏
                 // 用 stringify! 宏将输入的类型名转成字符串
                 stringify!($class)
             }
+            fn obj_type(&self) -> $crate::object::ObjectType {
+                $crate::object::ObjectType::$ty
+            }
             // 注意宏里面的类型要写完整路径，例如：alloc::string::String
             fn name(&self) -> alloc::string::String {
                 self.base.name()
             }
-            fn set_name(&self, name: &str){
-                // 直接访问内部的 pub 方法
-                self.base.set_name(name)
+            fn signal(&self) -> Signal {
+                self.base.signal()
+            }
+            fn signal_set(&self, signal: Signal) {
+                self.base.signal_set(signal)
+            }
+            fn signal_clear(&self, signal: Signal) {
+                self.base.signal_clear(signal)
+            }
+            fn signal_change(&self, clear: Signal, set: Signal) {
+                self.base.signal_change(clear, set)
+            }
+            fn add_signal_callback(&self, handler: SignalHandler) -> SignalCallbackId {
+                self.base.add_signal_callback(handler)
+            }
+            fn remove_signal_callback(&self, id: SignalCallbackId) {
+                self.base.remove_signal_callback(id)
+            }
+            fn handle_counter(&self) -> &$crate::object::HandleCounter {
+                self.base.handle_counter()
+            }
+            fn base(&self) -> &$crate::object::KObjectBase {
+                &self.base
             }
             // 可以传入任意数量的函数，覆盖 trait 的默认实现
             //$( ... )* 是一个重复模式，表示括号内的代码可以出现零次或多次，直到宏定义的结束。
@@ -112,7 +261,7 @@ pub struct DummyObject {
 }
 
 // 使用刚才的宏，声明其为内核对象，自动生成必要的代码，这就是模拟继承
-impl_kobject!(DummyObject);
+impl_kobject!(DummyObject, NONE);
 
 impl DummyObject {
     /// 创建一个新 `DummyObject`
@@ -122,4 +271,51 @@ impl DummyObject {
             base: KObjectBase::default(),
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod object_imp_test {
+    use super::*;
+    use crate::error::ZxError;
+    use crate::time;
+    use alloc::boxed::Box;
+
+    #[test]
+    fn callback_reenter() {
+        let object = DummyObject::new();
+        let weak = Arc::downgrade(&object);
+        // 回调里读同一个对象的信号，不会死锁
+        object.add_signal_callback(Box::new(move |signal| {
+            let object = weak.upgrade().unwrap();
+            assert_eq!(object.signal(), signal);
+            signal.contains(Signal::SIGNALED)
+        }));
+        object.signal_set(Signal::READABLE);
+        object.signal_set(Signal::SIGNALED);
+        assert!(object.base.inner.lock().signal_callbacks.is_empty());
+    }
+
+    #[test]
+    fn remove_callback() {
+        let object = DummyObject::new();
+        let id0 = object.add_signal_callback(Box::new(|_| false));
+        let id1 = object.add_signal_callback(Box::new(|_| false));
+        assert_ne!(id0, id1);
+        object.remove_signal_callback(id0);
+        object.remove_signal_callback(id0);
+        let inner = object.base.inner.lock();
+        assert_eq!(inner.signal_callbacks.len(), 1);
+        assert_eq!(inner.signal_callbacks[0].0, id1);
+    }
+
+    #[test]
+    fn wait_timeout_removes_callback() {
+        let object = DummyObject::new();
+        let dyn_object: Arc<dyn KernelObject> = object.clone();
+        assert_eq!(
+            object_wait_one(&dyn_object, Signal::READABLE, time::INFINITE_PAST).err(),
+            Some(ZxError::TIMED_OUT)
+        );
+        assert!(object.base.inner.lock().signal_callbacks.is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+/// 内核对象的类型，编号和 Zircon 的 `ZX_OBJ_TYPE_*` 一致，可以直接放进系统调用的参数和返回值里
+#[allow(non_camel_case_types)]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ObjectType {
+    /// 没有对应的 Zircon 类型，例如 DummyObject。
+    /// 它是一个普通的类型值，`Some(NONE)` 只匹配 NONE 类型的对象，不检查类型要用 `None`
+    NONE = 0,
+    PROCESS = 1,
+    THREAD = 2,
+    VMO = 3,
+    CHANNEL = 4,
+    EVENT = 5,
+    PORT = 6,
+    INTERRUPT = 9,
+    PCI_DEVICE = 11,
+    LOG = 12,
+    SOCKET = 14,
+    RESOURCE = 15,
+    EVENTPAIR = 16,
+    JOB = 17,
+    VMAR = 18,
+    FIFO = 19,
+    GUEST = 20,
+    VCPU = 21,
+    TIMER = 22,
+    IOMMU = 23,
+    BTI = 24,
+    PROFILE = 25,
+    PMT = 26,
+    SUSPEND_TOKEN = 27,
+    PAGER = 28,
+    EXCEPTION = 29,
+    CLOCK = 30,
+    STREAM = 31,
+    MSI = 32,
+}
+
+impl From<ObjectType> for u32 {
+    fn from(ty: ObjectType) -> u32 {
+        ty as u32
+    }
+}
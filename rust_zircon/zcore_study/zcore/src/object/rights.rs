@@ -4,13 +4,91 @@ use bitflags::bitflags;
 bitflags! {
     /// 句柄权限,借助 bitflags! 将一个 u32 的 rights 包装为一个 Rights 结构体
     pub struct Rights: u32 {
+        /// 可以复制句柄
         const DUPLICATE = 1 << 0;
+        /// 可以通过通道把句柄转移给别的进程
         const TRANSFER = 1 << 1;
+        /// 可以读取对象的数据
         const READ = 1 << 2;
+        /// 可以向对象写入数据
         const WRITE = 1 << 3;
+        /// 可以把对象映射为可执行
         const EXECUTE = 1 << 4;
-        const BASIC = 1 << 5;
-        const DEFAULT_PROCESS = 1 << 5;
+        /// 可以把对象映射到地址空间
+        const MAP = 1 << 5;
+        /// 可以读取对象的属性
+        const GET_PROPERTY = 1 << 6;
+        /// 可以设置对象的属性
+        const SET_PROPERTY = 1 << 7;
+        /// 可以枚举对象的子对象
+        const ENUMERATE = 1 << 8;
+        /// 可以销毁对象
+        const DESTROY = 1 << 9;
+        /// 可以设置作业的策略
+        const SET_POLICY = 1 << 10;
+        /// 可以读取作业的策略
+        const GET_POLICY = 1 << 11;
+        /// 可以置位和清除对象的用户信号
+        const SIGNAL = 1 << 12;
+        /// 可以置位和清除对端的用户信号
+        const SIGNAL_PEER = 1 << 13;
+        /// 可以等待对象的信号
+        const WAIT = 1 << 14;
+        /// 可以查询对象的信息
+        const INSPECT = 1 << 15;
+        /// 可以管理作业，例如创建子作业
+        const MANAGE_JOB = 1 << 16;
+        /// 可以管理进程，例如在其中创建线程
+        const MANAGE_PROCESS = 1 << 17;
+        /// 可以管理线程，例如挂起和恢复
+        const MANAGE_THREAD = 1 << 18;
+        /// 可以给线程应用调度配置
+        const APPLY_PROFILE = 1 << 19;
+        /// 可以管理套接字，例如设置读写阈值
+        const MANAGE_SOCKET = 1 << 20;
+        /// 复制或替换句柄时表示保持原权限
+        const SAME_RIGHTS = 1 << 31;
 
+        /// 几乎所有对象都有的基本权限
+        const BASIC = Self::TRANSFER.bits | Self::DUPLICATE.bits | Self::WAIT.bits | Self::INSPECT.bits;
+        /// 读写
+        const IO = Self::READ.bits | Self::WRITE.bits;
+        /// 读写属性
+        const PROPERTY = Self::GET_PROPERTY.bits | Self::SET_PROPERTY.bits;
+        /// 读写策略
+        const POLICY = Self::GET_POLICY.bits | Self::SET_POLICY.bits;
+
+        /// 通道句柄的默认权限，通道不能复制
+        const DEFAULT_CHANNEL = (Self::BASIC.bits & !Self::DUPLICATE.bits)
+            | Self::IO.bits | Self::SIGNAL.bits | Self::SIGNAL_PEER.bits;
+        /// 进程句柄的默认权限
+        const DEFAULT_PROCESS = Self::BASIC.bits | Self::IO.bits | Self::PROPERTY.bits
+            | Self::ENUMERATE.bits | Self::DESTROY.bits | Self::SIGNAL.bits
+            | Self::MANAGE_PROCESS.bits | Self::MANAGE_THREAD.bits;
+        /// 线程句柄的默认权限
+        const DEFAULT_THREAD = Self::BASIC.bits | Self::IO.bits | Self::PROPERTY.bits
+            | Self::DESTROY.bits | Self::SIGNAL.bits | Self::MANAGE_THREAD.bits;
+        /// 作业句柄的默认权限
+        const DEFAULT_JOB = Self::BASIC.bits | Self::IO.bits | Self::PROPERTY.bits
+            | Self::POLICY.bits | Self::ENUMERATE.bits | Self::DESTROY.bits | Self::SIGNAL.bits
+            | Self::MANAGE_JOB.bits | Self::MANAGE_PROCESS.bits | Self::MANAGE_THREAD.bits;
+        /// VMO 句柄的默认权限
+        const DEFAULT_VMO = Self::BASIC.bits | Self::IO.bits | Self::PROPERTY.bits
+            | Self::MAP.bits | Self::SIGNAL.bits;
+        /// VMAR 句柄的默认权限，VMAR 没有信号可等
+        const DEFAULT_VMAR = Self::BASIC.bits & !Self::WAIT.bits;
+        /// 事件句柄的默认权限
+        const DEFAULT_EVENT = Self::BASIC.bits | Self::SIGNAL.bits;
+        /// 事件对句柄的默认权限
+        const DEFAULT_EVENTPAIR = Self::BASIC.bits | Self::SIGNAL.bits | Self::SIGNAL_PEER.bits;
+        /// 端口句柄的默认权限
+        const DEFAULT_PORT = (Self::BASIC.bits & !Self::WAIT.bits) | Self::IO.bits;
+        /// 定时器句柄的默认权限
+        const DEFAULT_TIMER = Self::BASIC.bits | Self::WRITE.bits | Self::SIGNAL.bits;
+        /// 套接字句柄的默认权限
+        const DEFAULT_SOCKET = Self::BASIC.bits | Self::IO.bits | Self::PROPERTY.bits
+            | Self::SIGNAL.bits | Self::SIGNAL_PEER.bits;
+        /// FIFO 句柄的默认权限
+        const DEFAULT_FIFO = Self::BASIC.bits | Self::IO.bits | Self::SIGNAL.bits | Self::SIGNAL_PEER.bits;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,216 @@
+use super::KernelObject;
+use crate::error::*;
+use crate::time::Time;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+bitflags! {
+    /// 对象信号，每个内核对象都带有一组信号，用来表示对象当前的状态，
+    /// 等待者可以通过等待信号来得知对象的状态变化，而不用反复去查询。
+    #[derive(Default)]
+    pub struct Signal: u32 {
+        /// 对象可读，例如 channel 中有消息
+        const READABLE = 1 << 0;
+        /// 对象可写
+        const WRITABLE = 1 << 1;
+        /// 对端已经关闭
+        const PEER_CLOSED = 1 << 2;
+        /// 对象被触发，例如进程或线程终止
+        const SIGNALED = 1 << 3;
+        /// socket 的对端不会再写入数据了
+        const PEER_WRITE_DISABLED = 1 << 4;
+        /// socket 自己不能再写入数据了
+        const WRITE_DISABLED = 1 << 5;
+        /// socket 缓冲的数据量达到了读阈值
+        const READ_THRESHOLD = 1 << 10;
+        /// socket 对端缓冲区的剩余空间达到了写阈值
+        const WRITE_THRESHOLD = 1 << 11;
+
+        /// 用户信号，可以由用户自由地置位和清除
+        const USER_SIGNAL_0 = 1 << 24;
+        const USER_SIGNAL_1 = 1 << 25;
+        const USER_SIGNAL_2 = 1 << 26;
+        const USER_SIGNAL_3 = 1 << 27;
+        const USER_SIGNAL_4 = 1 << 28;
+        const USER_SIGNAL_5 = 1 << 29;
+        const USER_SIGNAL_6 = 1 << 30;
+        const USER_SIGNAL_7 = 1 << 31;
+        const USER_ALL = 0xff << 24;
+    }
+}
+
+/// 信号回调，参数是变化后的信号，返回 true 表示这个回调已经完成使命，可以移除了
+pub type SignalHandler = Box<dyn Fn(Signal) -> bool + Send>;
+
+/// 信号回调的编号，用来注销回调
+pub type SignalCallbackId = u64;
+
+/// `object_wait_many` 的等待项
+pub struct WaitItem {
+    /// 要等待的对象
+    pub object: Arc<dyn KernelObject>,
+    /// 要等待的信号，任意一个被置位就算满足
+    pub waitfor: Signal,
+    /// 等待结束时对象上的信号
+    pub pending: Signal,
+}
+
+/// 等待对象 `object` 上 `signal` 中的任意一个信号被置位，或者到达截止时间 `deadline`。
+/// 成功时返回对象当前的信号，超时返回 TIMED_OUT。
+pub fn object_wait_one(
+    object: &Arc<dyn KernelObject>,
+    signal: Signal,
+    deadline: Time,
+) -> ZxResult<Signal> {
+    let mut items = [WaitItem {
+        object: object.clone(),
+        waitfor: signal,
+        pending: Signal::empty(),
+    }];
+    object_wait_many(&mut items, deadline)?;
+    Ok(items[0].pending)
+}
+
+/// 同时等待多个对象，任意一个对象满足条件就返回，
+/// 每一项的 `pending` 会被填上等待结束时对象上的信号。超时返回 TIMED_OUT。
+pub fn object_wait_many(items: &mut [WaitItem], deadline: Time) -> ZxResult<()> {
+    //所有回调共享同一个唤醒标志，这里没有调度器，等待者只能在标志上自旋
+    let woken = Arc::new(AtomicBool::new(false));
+    let ids: Vec<_> = items
+        .iter()
+        .map(|item| {
+            let waitfor = item.waitfor;
+            let woken = woken.clone();
+            item.object.add_signal_callback(Box::new(move |signal| {
+                if signal.intersects(waitfor) {
+                    woken.store(true, Ordering::SeqCst);
+                    return true;
+                }
+                false
+            }))
+        })
+        .collect();
+    let result = loop {
+        if woken.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+        if deadline.is_expired() {
+            break Err(ZxError::TIMED_OUT);
+        }
+        core::hint::spin_loop();
+    };
+    //无论是否超时都注销回调，否则信号不再变化的对象上会一直留着这些回调
+    for (item, id) in items.iter_mut().zip(ids) {
+        item.object.remove_signal_callback(id);
+        item.pending = item.object.signal();
+    }
+    result
+}
+
+#[cfg(test)]
+mod signal_test {
+    use super::*;
+    use crate::object::DummyObject;
+    use crate::time::{self, Duration};
+    use std::thread;
+
+    #[test]
+    fn signal_change() {
+        let object: Arc<dyn KernelObject> = DummyObject::new();
+        assert_eq!(object.signal(), Signal::empty());
+        object.signal_set(Signal::READABLE | Signal::USER_SIGNAL_0);
+        assert_eq!(object.signal(), Signal::READABLE | Signal::USER_SIGNAL_0);
+        object.signal_change(Signal::READABLE, Signal::WRITABLE);
+        assert_eq!(object.signal(), Signal::WRITABLE | Signal::USER_SIGNAL_0);
+        object.signal_clear(Signal::all());
+        assert_eq!(object.signal(), Signal::empty());
+    }
+
+    #[test]
+    fn callback() {
+        use core::sync::atomic::AtomicU32;
+        let object: Arc<dyn KernelObject> = DummyObject::new();
+        let count = Arc::new(AtomicU32::new(0));
+        let count1 = count.clone();
+        object.add_signal_callback(Box::new(move |signal| {
+            count1.fetch_add(1, Ordering::SeqCst);
+            signal.contains(Signal::SIGNALED)
+        }));
+        // 添加时调用一次
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        object.signal_set(Signal::READABLE);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        // 信号没有变化不会调用
+        object.signal_set(Signal::READABLE);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        // 返回 true 之后回调被移除
+        object.signal_set(Signal::SIGNALED);
+        object.signal_clear(Signal::SIGNALED);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn wait_one() {
+        let object: Arc<dyn KernelObject> = DummyObject::new();
+        // 截止时间已过且信号不满足，立刻超时
+        assert_eq!(
+            object_wait_one(&object, Signal::READABLE, time::INFINITE_PAST).err(),
+            Some(ZxError::TIMED_OUT)
+        );
+        object.signal_set(Signal::READABLE);
+        assert_eq!(
+            object_wait_one(&object, Signal::READABLE, time::INFINITE_PAST),
+            Ok(Signal::READABLE)
+        );
+
+        // 另一个线程置位信号，唤醒等待者
+        let object1 = object.clone();
+        let waiter = thread::spawn(move || {
+            object_wait_one(&object1, Signal::USER_SIGNAL_0, time::INFINITE)
+        });
+        object.signal_set(Signal::USER_SIGNAL_0);
+        assert_eq!(
+            waiter.join().unwrap(),
+            Ok(Signal::READABLE | Signal::USER_SIGNAL_0)
+        );
+    }
+
+    #[test]
+    fn wait_many() {
+        let object0: Arc<dyn KernelObject> = DummyObject::new();
+        let object1: Arc<dyn KernelObject> = DummyObject::new();
+        let mut items = [
+            WaitItem {
+                object: object0.clone(),
+                waitfor: Signal::READABLE,
+                pending: Signal::empty(),
+            },
+            WaitItem {
+                object: object1.clone(),
+                waitfor: Signal::SIGNALED,
+                pending: Signal::empty(),
+            },
+        ];
+        assert_eq!(object_wait_many(&mut items, time::INFINITE_PAST).err(), Some(ZxError::TIMED_OUT));
+
+        object1.signal_set(Signal::SIGNALED | Signal::WRITABLE);
+        object_wait_many(&mut items, time::INFINITE).unwrap();
+        assert_eq!(items[0].pending, Signal::empty());
+        assert_eq!(items[1].pending, Signal::SIGNALED | Signal::WRITABLE);
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let object: Arc<dyn KernelObject> = DummyObject::new();
+        let _clock = time::CLOCK_TEST_LOCK.lock();
+        let deadline = Time::after(Duration::from_nanos(1000));
+        let object1 = object.clone();
+        let waiter = thread::spawn(move || object_wait_one(&object1, Signal::READABLE, deadline));
+        // 时钟走过截止时间，等待者超时
+        time::advance(Duration::from_nanos(1000));
+        assert_eq!(waiter.join().unwrap().err(), Some(ZxError::TIMED_OUT));
+    }
+}
@@ -1,2 +1,20 @@
 pub mod process;
-pub use self::process::*;
\ No newline at end of file
+pub use self::process::*;
+
+pub mod thread;
+pub use self::thread::*;
+
+pub mod job;
+pub use self::job::*;
+
+pub mod futex;
+pub use self::futex::*;
+
+pub mod handle_table;
+pub use self::handle_table::*;
+
+pub mod info;
+pub use self::info::*;
+
+pub mod property;
+pub use self::property::*;
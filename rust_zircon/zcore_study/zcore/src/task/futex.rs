@@ -0,0 +1,304 @@
+use spin::Mutex;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::*;
+use crate::time::Time;
+use crate::user::{AddressSpace, UserInPtr, UserPtr};
+use super::Thread;
+
+/// 一个进程的 futex 表。
+/// futex 没有句柄，它由（地址空间，地址）唯一确定：每个进程有一张表，表中的 key 就是用户地址。
+/// 整张表只用一把锁保护，这样 requeue 在两个队列之间搬运等待者时不需要同时拿两把锁。
+#[derive(Default)]
+pub struct FutexTable {
+    inner: Mutex<BTreeMap<usize, FutexQueue>>,
+}
+
+/// 一个 futex 上的等待队列
+#[derive(Default)]
+struct FutexQueue {
+    waiters: VecDeque<Arc<FutexWaiter>>, //按等待的先后排队
+    owner: Option<Arc<Thread>>,          //futex 的持有者，用于优先级继承
+}
+
+/// 一个等待者，对应一个阻塞在 futex 上的线程，被唤醒时置位 `woken`
+struct FutexWaiter {
+    woken: AtomicBool,
+}
+
+impl FutexQueue {
+    fn is_empty(&self) -> bool {
+        self.waiters.is_empty() && self.owner.is_none()
+    }
+}
+
+impl FutexTable {
+    /// 如果地址 `addr` 处的值等于 `expected`，就让线程 `thread` 阻塞在这个 futex 上，
+    /// 直到被唤醒或者到达截止时间 `deadline`，同时把 futex 的持有者设为 `new_owner`。
+    ///
+    /// 值不相等返回 BAD_STATE，超时返回 TIMED_OUT，等待期间线程被杀死返回 CANCELED。
+    pub fn wait(
+        &self,
+        aspace: &dyn AddressSpace,
+        thread: &Arc<Thread>,
+        addr: usize,
+        expected: i32,
+        new_owner: Option<Arc<Thread>>,
+        deadline: Time,
+    ) -> ZxResult<()> {
+        if let Some(owner) = &new_owner {
+            if Arc::ptr_eq(owner, thread) {
+                return Err(ZxError::INVALID_ARGS); //不能在等待的同时持有自己等的锁
+            }
+        }
+        let waiter = Arc::new(FutexWaiter {
+            woken: AtomicBool::new(false),
+        });
+        {
+            //比较和入队必须在同一把锁下完成，否则可能错过在这之间发生的唤醒
+            let mut table = self.inner.lock();
+            let ptr: UserInPtr<i32> = UserPtr::new(aspace, addr);
+            if ptr.read()? != expected {
+                return Err(ZxError::BAD_STATE);
+            }
+            thread.block()?;
+            let queue = table.entry(addr).or_default();
+            queue.owner = new_owner;
+            queue.waiters.push_back(waiter.clone());
+        }
+        let result = loop {
+            if waiter.woken.load(Ordering::SeqCst) {
+                break Ok(());
+            }
+            if thread.is_dying() {
+                break Err(ZxError::CANCELED);
+            }
+            if deadline.is_expired() {
+                break Err(ZxError::TIMED_OUT);
+            }
+            core::hint::spin_loop();
+        };
+        let result = match result {
+            Ok(()) => Ok(()),
+            Err(err) => self.cancel(&waiter, err),
+        };
+        let _ = thread.unblock(); //线程在等待期间被杀死时状态已经是 Dying，不需要再恢复
+        result
+    }
+    /// 等待失败时把等待者从队列中摘下来。
+    /// 如果在摘下之前它已经被唤醒了，唤醒优先，返回成功。
+    fn cancel(&self, waiter: &Arc<FutexWaiter>, err: ZxError) -> ZxResult<()> {
+        let mut table = self.inner.lock();
+        if waiter.woken.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        for queue in table.values_mut() {
+            queue.waiters.retain(|w| !Arc::ptr_eq(w, waiter));
+        }
+        table.retain(|_, q| !q.is_empty());
+        Err(err)
+    }
+    /// 唤醒地址 `addr` 上最多 `count` 个等待者，futex 的持有者被清空
+    pub fn wake(&self, addr: usize, count: usize) -> ZxResult<()> {
+        if !addr.is_multiple_of(4) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let mut table = self.inner.lock();
+        if let Some(queue) = table.get_mut(&addr) {
+            queue.owner = None;
+            Self::wake_waiters(queue, count);
+            if queue.is_empty() {
+                table.remove(&addr);
+            }
+        }
+        Ok(())
+    }
+    /// 如果地址 `addr` 处的值等于 `expected`，唤醒 `addr` 上最多 `wake_count` 个等待者，
+    /// 再把最多 `requeue_count` 个等待者搬到 `requeue_addr` 上，并把那里的持有者设为 `requeue_owner`。
+    #[allow(clippy::too_many_arguments)]
+    pub fn requeue(
+        &self,
+        aspace: &dyn AddressSpace,
+        addr: usize,
+        wake_count: usize,
+        expected: i32,
+        requeue_addr: usize,
+        requeue_count: usize,
+        requeue_owner: Option<Arc<Thread>>,
+    ) -> ZxResult<()> {
+        if addr == requeue_addr || !requeue_addr.is_multiple_of(4) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let mut table = self.inner.lock();
+        let ptr: UserInPtr<i32> = UserPtr::new(aspace, addr);
+        if ptr.read()? != expected {
+            return Err(ZxError::BAD_STATE);
+        }
+        let mut moved = VecDeque::new();
+        if let Some(queue) = table.get_mut(&addr) {
+            queue.owner = None;
+            Self::wake_waiters(queue, wake_count);
+            let n = requeue_count.min(queue.waiters.len());
+            moved.extend(queue.waiters.drain(..n));
+            if queue.is_empty() {
+                table.remove(&addr);
+            }
+        }
+        let target = table.entry(requeue_addr).or_default();
+        target.waiters.extend(moved);
+        target.owner = requeue_owner;
+        if target.is_empty() {
+            table.remove(&requeue_addr);
+        }
+        Ok(())
+    }
+    /// 获取地址 `addr` 上 futex 的持有者
+    pub fn owner(&self, addr: usize) -> Option<Arc<Thread>> {
+        self.inner.lock().get(&addr).and_then(|q| q.owner.clone())
+    }
+    /// 地址 `addr` 上正在等待的线程数
+    pub fn waiter_count(&self, addr: usize) -> usize {
+        self.inner.lock().get(&addr).map_or(0, |q| q.waiters.len())
+    }
+    fn wake_waiters(queue: &mut FutexQueue, count: usize) {
+        let n = count.min(queue.waiters.len());
+        for waiter in queue.waiters.drain(..n) {
+            waiter.woken.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod futex_test {
+    use super::*;
+    use crate::task::{Process, ThreadState};
+    use crate::vm::{MMUFlags, Vmo, PAGE_SIZE};
+    use crate::user::UserOutPtr;
+    use crate::time;
+    use std::thread;
+
+    /// 创建一个映射了一页内存的进程，返回进程和这一页的地址
+    fn setup() -> (Arc<Process>, usize) {
+        let proc = Process::new();
+        let vmo = Vmo::new(PAGE_SIZE).unwrap();
+        let addr = proc
+            .vmar()
+            .map(None, vmo, 0, PAGE_SIZE, MMUFlags::READ | MMUFlags::WRITE)
+            .unwrap();
+        (proc, addr)
+    }
+
+    /// 创建一个正在运行的线程
+    fn running_thread(proc: &Arc<Process>) -> Arc<Thread> {
+        let thread = Thread::create(proc, "waiter").unwrap();
+        thread.start().unwrap();
+        thread
+    }
+
+    /// 在宿主线程中让 `thread` 等待 `addr`，并一直等到它真的阻塞了
+    fn spawn_waiter(
+        proc: &Arc<Process>,
+        thread: &Arc<Thread>,
+        addr: usize,
+    ) -> thread::JoinHandle<ZxResult<()>> {
+        let (proc1, thread1) = (proc.clone(), thread.clone());
+        let handle = thread::spawn(move || {
+            proc1.futex_wait(&thread1, addr, 0, None, time::INFINITE)
+        });
+        while thread.state() != ThreadState::Blocked {
+            core::hint::spin_loop();
+        }
+        handle
+    }
+
+    #[test]
+    fn wait_wake() {
+        let (proc, addr) = setup();
+        let t0 = running_thread(&proc);
+        let t1 = running_thread(&proc);
+
+        // 值不相等，立刻返回
+        let ptr: UserOutPtr<i32> = UserPtr::new(&*proc, addr);
+        ptr.write(1).unwrap();
+        assert_eq!(
+            proc.futex_wait(&t0, addr, 0, None, time::INFINITE).err(),
+            Some(ZxError::BAD_STATE)
+        );
+        ptr.write(0).unwrap();
+
+        let h0 = spawn_waiter(&proc, &t0, addr);
+        let h1 = spawn_waiter(&proc, &t1, addr);
+        assert_eq!(proc.futex_waiter_count(addr), 2);
+
+        // 先进先出，一个一个地唤醒
+        proc.futex_wake(addr, 1).unwrap();
+        assert_eq!(h0.join().unwrap(), Ok(()));
+        assert_eq!(t0.state(), ThreadState::Running);
+        assert_eq!(t1.state(), ThreadState::Blocked);
+        proc.futex_wake(addr, usize::MAX).unwrap();
+        assert_eq!(h1.join().unwrap(), Ok(()));
+        assert_eq!(proc.futex_waiter_count(addr), 0);
+    }
+
+    #[test]
+    fn timeout_and_kill() {
+        let (proc, addr) = setup();
+        let thread = running_thread(&proc);
+        assert_eq!(
+            proc.futex_wait(&thread, addr, 0, None, time::INFINITE_PAST).err(),
+            Some(ZxError::TIMED_OUT)
+        );
+        assert_eq!(thread.state(), ThreadState::Running);
+        assert_eq!(proc.futex_waiter_count(addr), 0);
+
+        // 等待期间被杀死
+        let handle = spawn_waiter(&proc, &thread, addr);
+        thread.kill();
+        assert_eq!(handle.join().unwrap().err(), Some(ZxError::CANCELED));
+        assert_eq!(thread.state(), ThreadState::Dying);
+        assert_eq!(proc.futex_waiter_count(addr), 0);
+    }
+
+    #[test]
+    fn requeue_and_owner() {
+        let (proc, addr) = setup();
+        let addr2 = addr + 4;
+        let owner = running_thread(&proc);
+        let t0 = running_thread(&proc);
+        let t1 = running_thread(&proc);
+        let t2 = running_thread(&proc);
+
+        // 持有者不能是等待者自己
+        assert_eq!(
+            proc.futex_wait(&t0, addr, 0, Some(t0.clone()), time::INFINITE_PAST).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        let h0 = spawn_waiter(&proc, &t0, addr);
+        let h1 = spawn_waiter(&proc, &t1, addr);
+        let h2 = spawn_waiter(&proc, &t2, addr);
+
+        assert_eq!(
+            proc.futex_requeue(addr, 1, 1, addr2, 1, None).err(),
+            Some(ZxError::BAD_STATE)
+        );
+        assert_eq!(
+            proc.futex_requeue(addr, 1, 0, addr, 1, None).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        // 唤醒一个，搬走一个，剩下一个
+        proc.futex_requeue(addr, 1, 0, addr2, 1, Some(owner.clone())).unwrap();
+        assert_eq!(h0.join().unwrap(), Ok(()));
+        assert_eq!(proc.futex_waiter_count(addr), 1);
+        assert_eq!(proc.futex_waiter_count(addr2), 1);
+        assert!(Arc::ptr_eq(&proc.futex_owner(addr2).unwrap(), &owner));
+
+        // 唤醒之后持有者被清空
+        proc.futex_wake(addr2, 1).unwrap();
+        assert_eq!(h1.join().unwrap(), Ok(()));
+        assert!(proc.futex_owner(addr2).is_none());
+        proc.futex_wake(addr, 1).unwrap();
+        assert_eq!(h2.join().unwrap(), Ok(()));
+    }
+}
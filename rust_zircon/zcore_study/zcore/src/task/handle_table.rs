@@ -0,0 +1,249 @@
+use alloc::vec::Vec;
+
+use crate::error::*;
+use crate::object::Handle;
+use super::HandleValue;
+
+/// 每个进程最多持有的句柄数
+pub const MAX_HANDLES_PER_PROCESS: usize = 256 * 1024;
+
+//句柄值的布局：最低 2 位固定为 1，中间是槽位下标，最高几位是槽位的代数。
+//固定位保证合法的句柄值永远不会是 0，代数保证槽位被重用之后旧的句柄值不会意外地又变得有效。
+const FIXED_BITS: u32 = 0b11;
+const INDEX_SHIFT: u32 = 2;
+const INDEX_BITS: u32 = 18;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+const GENERATION_SHIFT: u32 = INDEX_SHIFT + INDEX_BITS;
+//代数只有 12 位：同一个槽位被重用 4096 次之后代数回绕，最早的那个句柄值会重新变得有效。
+//Zircon 的句柄值也有同样的限制，这里只保证在回绕之前过时的句柄值一定无效。
+const GENERATION_MASK: u32 = (1 << (32 - GENERATION_SHIFT)) - 1;
+
+/// 进程的句柄表，用 slab 实现，添加、删除和查找都是 O(1) 的
+pub struct HandleTable {
+    slots: Vec<Slot>,
+    free: Vec<u32>, //空闲槽位的下标
+    count: usize,   //当前的句柄数，包括被暂时取出的句柄
+    reserved: usize, //为还没到达的句柄预留的位置数
+    limit: usize,   //句柄数上限
+}
+
+/// 句柄表中的一个槽位
+struct Slot {
+    generation: u32,        //槽位每被释放一次就加一
+    handle: Option<Handle>, //空闲槽位为 None
+}
+
+impl Default for HandleTable {
+    fn default() -> Self {
+        Self::with_limit(MAX_HANDLES_PER_PROCESS)
+    }
+}
+
+impl HandleTable {
+    /// 创建一个最多容纳 `limit` 个句柄的句柄表，`limit` 不能超过句柄值能编码的槽位数
+    pub fn with_limit(limit: usize) -> Self {
+        HandleTable {
+            slots: Vec::new(),
+            free: Vec::new(),
+            count: 0,
+            reserved: 0,
+            limit: limit.min(INDEX_MASK as usize + 1),
+        }
+    }
+    /// 当前的句柄数
+    pub fn len(&self) -> usize {
+        self.count
+    }
+    /// 句柄表是否为空
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+    /// 还能再添加的句柄数
+    pub fn remaining(&self) -> usize {
+        self.limit - self.count - self.reserved
+    }
+    /// 添加一个句柄，返回它的句柄值，句柄数达到上限时返回 NO_RESOURCES
+    pub fn add(&mut self, handle: Handle) -> ZxResult<HandleValue> {
+        if self.remaining() == 0 {
+            return Err(ZxError::NO_RESOURCES);
+        }
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    handle: None,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.handle = Some(handle);
+        self.count += 1;
+        Ok(encode(index, slot.generation))
+    }
+    /// 查找句柄，句柄值无效或已经过时返回 BAD_HANDLE
+    pub fn get(&self, value: HandleValue) -> ZxResult<&Handle> {
+        let index = self.index_of(value)?;
+        Ok(self.slots[index].handle.as_ref().unwrap())
+    }
+    /// 移除句柄并返回它，句柄值无效或已经过时返回 BAD_HANDLE
+    pub fn remove(&mut self, value: HandleValue) -> ZxResult<Handle> {
+        let handle = self.take(value)?;
+        self.release(value);
+        Ok(handle)
+    }
+    /// 暂时取出句柄：句柄值马上失效，但槽位还留着，
+    /// 之后要么用 `put_back` 原样放回，要么用 `release` 真正释放槽位
+    pub(super) fn take(&mut self, value: HandleValue) -> ZxResult<Handle> {
+        let index = self.index_of(value)?;
+        Ok(self.slots[index].handle.take().unwrap())
+    }
+    /// 把 `take` 取出的句柄放回原来的槽位，句柄值和取出之前一样
+    pub(super) fn put_back(&mut self, value: HandleValue, handle: Handle) {
+        let slot = &mut self.slots[decode_index(value)];
+        debug_assert!(slot.handle.is_none());
+        slot.handle = Some(handle);
+    }
+    /// 释放 `take` 取出句柄之后留下的槽位
+    pub(super) fn release(&mut self, value: HandleValue) {
+        let index = decode_index(value);
+        let slot = &mut self.slots[index];
+        debug_assert!(slot.handle.is_none());
+        slot.generation = (slot.generation + 1) & GENERATION_MASK;
+        self.free.push(index as u32);
+        self.count -= 1;
+    }
+    /// 为之后要添加的句柄预留最多 `n` 个位置，返回实际预留的个数。
+    /// 预留的位置只能用 `add_reserved` 使用，用不完的要用 `unreserve` 还回来
+    pub(super) fn reserve(&mut self, n: usize) -> usize {
+        let n = n.min(self.remaining());
+        self.reserved += n;
+        n
+    }
+    /// 归还 `n` 个预留的位置
+    pub(super) fn unreserve(&mut self, n: usize) {
+        self.reserved -= n;
+    }
+    /// 用一个预留的位置添加句柄，一定成功
+    pub(super) fn add_reserved(&mut self, handle: Handle) -> HandleValue {
+        self.unreserve(1);
+        self.add(handle).unwrap()
+    }
+    /// 句柄值是否有效
+    pub fn contains(&self, value: HandleValue) -> bool {
+        self.index_of(value).is_ok()
+    }
+    /// 按槽位顺序遍历所有句柄
+    pub fn iter(&self) -> impl Iterator<Item = (HandleValue, &Handle)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let handle = slot.handle.as_ref()?;
+            Some((encode(index as u32, slot.generation), handle))
+        })
+    }
+    /// 解码句柄值，检查它指向一个正在使用、代数相同的槽位
+    fn index_of(&self, value: HandleValue) -> ZxResult<usize> {
+        if value & FIXED_BITS != FIXED_BITS {
+            return Err(ZxError::BAD_HANDLE);
+        }
+        let index = decode_index(value);
+        let generation = value >> GENERATION_SHIFT;
+        match self.slots.get(index) {
+            Some(slot) if slot.generation == generation && slot.handle.is_some() => Ok(index),
+            _ => Err(ZxError::BAD_HANDLE),
+        }
+    }
+}
+
+fn encode(index: u32, generation: u32) -> HandleValue {
+    generation << GENERATION_SHIFT | index << INDEX_SHIFT | FIXED_BITS
+}
+
+fn decode_index(value: HandleValue) -> usize {
+    ((value >> INDEX_SHIFT) & INDEX_MASK) as usize
+}
+
+#[cfg(test)]
+mod handle_table_test {
+    use super::*;
+    use crate::object::{DummyObject, Rights};
+
+    fn handle() -> Handle {
+        Handle::new(DummyObject::new(), Rights::BASIC)
+    }
+
+    #[test]
+    fn add_remove() {
+        let mut table = HandleTable::default();
+        let h0 = table.add(handle()).unwrap();
+        let h1 = table.add(handle()).unwrap();
+        assert_ne!(h0, h1);
+        assert_eq!(h0 & FIXED_BITS, FIXED_BITS);
+        assert_eq!(table.len(), 2);
+        assert!(table.get(h0).is_ok());
+        assert_eq!(table.get(0).err(), Some(ZxError::BAD_HANDLE));
+        assert_eq!(table.get(h1 + 4).err(), Some(ZxError::BAD_HANDLE));
+
+        // 槽位被重用之后，旧的句柄值仍然无效
+        table.remove(h0).unwrap();
+        assert_eq!(table.remove(h0).err(), Some(ZxError::BAD_HANDLE));
+        let h2 = table.add(handle()).unwrap();
+        assert_ne!(h2, h0);
+        assert_eq!(table.get(h0).err(), Some(ZxError::BAD_HANDLE));
+        assert!(table.contains(h2));
+        assert_eq!(table.iter().map(|(v, _)| v).collect::<Vec<_>>(), [h2, h1]);
+    }
+
+    #[test]
+    fn limit() {
+        let mut table = HandleTable::with_limit(2);
+        let h0 = table.add(handle()).unwrap();
+        table.add(handle()).unwrap();
+        assert_eq!(table.remaining(), 0);
+        assert_eq!(table.add(handle()).err(), Some(ZxError::NO_RESOURCES));
+        table.remove(h0).unwrap();
+        assert!(table.add(handle()).is_ok());
+    }
+
+    #[test]
+    fn take_and_reserve() {
+        let mut table = HandleTable::with_limit(3);
+        let h0 = table.add(handle()).unwrap();
+
+        // 取出的句柄值马上失效，但槽位不会被别人用掉，放回之后句柄值不变
+        let taken = table.take(h0).unwrap();
+        assert!(!table.contains(h0));
+        let h1 = table.add(handle()).unwrap();
+        assert_ne!(decode_index(h1), decode_index(h0));
+        table.put_back(h0, taken);
+        assert!(table.contains(h0));
+        table.take(h0).unwrap();
+        table.release(h0);
+        assert_eq!(table.len(), 1);
+
+        // 预留的位置别人用不了
+        assert_eq!(table.reserve(5), 2);
+        assert_eq!(table.add(handle()).err(), Some(ZxError::NO_RESOURCES));
+        let h2 = table.add_reserved(handle());
+        assert!(table.contains(h2));
+        table.unreserve(1);
+        assert_eq!(table.remaining(), 1);
+    }
+
+    #[test]
+    fn generation_wrap() {
+        let mut table = HandleTable::default();
+        let first = table.add(handle()).unwrap();
+        let mut value = first;
+        // 回绕之前，同一个槽位上旧的句柄值一直无效
+        for _ in 0..GENERATION_MASK {
+            table.remove(value).unwrap();
+            value = table.add(handle()).unwrap();
+            assert_ne!(value, first);
+            assert!(!table.contains(first));
+        }
+        // 第 4096 次重用时代数回绕，得到和最早一样的句柄值
+        table.remove(value).unwrap();
+        assert_eq!(table.add(handle()).unwrap(), first);
+    }
+}
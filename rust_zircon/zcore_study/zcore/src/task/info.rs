@@ -0,0 +1,188 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::error::*;
+use crate::object::*;
+use super::{HandleValue, Process};
+
+/// `get_info` 可以查询的主题
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfoTopic {
+    /// 句柄的基本信息，任何句柄都可以查询
+    HandleBasic,
+    /// 指向对象的句柄数，需要 INSPECT 权限
+    HandleCount,
+    /// 进程持有的各类对象的句柄数，需要进程句柄和 INSPECT 权限
+    ProcessHandleStats,
+    /// 进程的整张句柄表，需要进程句柄和 INSPECT 权限
+    HandleTable,
+}
+
+/// `get_info` 的查询结果，和查询的主题一一对应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectInfo {
+    HandleBasic(InfoHandleBasic),
+    HandleCount(u32),
+    ProcessHandleStats(InfoProcessHandleStats),
+    HandleTable(Vec<InfoHandleExtended>),
+}
+
+/// 句柄的基本信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoHandleBasic {
+    /// 对象的 ID
+    pub koid: KoID,
+    /// 句柄的权限
+    pub rights: Rights,
+    /// 对象的类型
+    pub obj_type: ObjectType,
+    /// 相关对象的 ID，例如通道的对端，没有时为 0
+    pub related_koid: KoID,
+}
+
+/// 进程持有的各类对象的句柄数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InfoProcessHandleStats {
+    /// 按对象类型统计的句柄数
+    pub handle_count: BTreeMap<ObjectType, u32>,
+}
+
+/// 句柄表中的一项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoHandleExtended {
+    /// 句柄值
+    pub handle: HandleValue,
+    /// 对象的 ID
+    pub koid: KoID,
+    /// 句柄的权限
+    pub rights: Rights,
+    /// 对象的类型
+    pub obj_type: ObjectType,
+    /// 相关对象的 ID
+    pub related_koid: KoID,
+}
+
+impl InfoHandleBasic {
+    fn new(object: &Arc<dyn KernelObject>, rights: Rights) -> Self {
+        InfoHandleBasic {
+            koid: object.id(),
+            rights,
+            obj_type: object.obj_type(),
+            related_koid: object.related_koid(),
+        }
+    }
+}
+
+impl Process {
+    /// 查询句柄 `handle_value` 指向的对象在主题 `topic` 下的信息
+    pub fn get_info(&self, handle_value: HandleValue, topic: InfoTopic) -> ZxResult<ObjectInfo> {
+        //只取出对象和权限，不复制句柄，免得查询本身让句柄数多出一个
+        let (object, rights) = self.with_handles(|handles| {
+            handles.get(handle_value).map(|h| (h.object.clone(), h.rights))
+        })?;
+        if topic != InfoTopic::HandleBasic && !rights.contains(Rights::INSPECT) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let info = match topic {
+            InfoTopic::HandleBasic => ObjectInfo::HandleBasic(InfoHandleBasic::new(&object, rights)),
+            InfoTopic::HandleCount => ObjectInfo::HandleCount(object.handle_count()),
+            InfoTopic::ProcessHandleStats => {
+                let mut stats = InfoProcessHandleStats::default();
+                Self::target_process(&object)?.with_handles(|handles| {
+                    for (_, h) in handles.iter() {
+                        *stats.handle_count.entry(h.object.obj_type()).or_default() += 1;
+                    }
+                });
+                ObjectInfo::ProcessHandleStats(stats)
+            }
+            InfoTopic::HandleTable => {
+                let table = Self::target_process(&object)?.with_handles(|handles| {
+                    handles
+                        .iter()
+                        .map(|(value, h)| {
+                            let basic = InfoHandleBasic::new(&h.object, h.rights);
+                            InfoHandleExtended {
+                                handle: value,
+                                koid: basic.koid,
+                                rights: basic.rights,
+                                obj_type: basic.obj_type,
+                                related_koid: basic.related_koid,
+                            }
+                        })
+                        .collect()
+                });
+                ObjectInfo::HandleTable(table)
+            }
+        };
+        Ok(info)
+    }
+    /// 进程相关的主题只能对进程句柄查询
+    fn target_process(object: &Arc<dyn KernelObject>) -> ZxResult<Arc<Process>> {
+        object
+            .clone()
+            .downcast_arc::<Process>()
+            .map_err(|_| ZxError::WRONG_TYPE)
+    }
+}
+
+#[cfg(test)]
+mod info_test {
+    use super::*;
+    use crate::ipc::Channel;
+
+    #[test]
+    fn handle_basic_and_count() {
+        let proc = Process::new();
+        let (channel0, channel1) = Channel::create();
+        let h0 = proc.add_handle(Handle::with_default_rights(channel0.clone())).unwrap();
+        let h1 = proc.add_handle(Handle::new(channel0.clone(), Rights::READ)).unwrap();
+
+        let info = proc.get_info(h0, InfoTopic::HandleBasic).unwrap();
+        assert_eq!(
+            info,
+            ObjectInfo::HandleBasic(InfoHandleBasic {
+                koid: channel0.id(),
+                rights: Rights::DEFAULT_CHANNEL,
+                obj_type: ObjectType::CHANNEL,
+                related_koid: channel1.id(),
+            })
+        );
+        assert_eq!(proc.get_info(h0, InfoTopic::HandleCount), Ok(ObjectInfo::HandleCount(2)));
+        //没有 INSPECT 权限只能查询基本信息
+        assert!(proc.get_info(h1, InfoTopic::HandleBasic).is_ok());
+        assert_eq!(proc.get_info(h1, InfoTopic::HandleCount).err(), Some(ZxError::ACCESS_DENIED));
+        proc.remove_handle(h1).unwrap();
+        assert_eq!(proc.get_info(h0, InfoTopic::HandleCount), Ok(ObjectInfo::HandleCount(1)));
+        assert_eq!(proc.get_info(h1, InfoTopic::HandleBasic).err(), Some(ZxError::BAD_HANDLE));
+        //通道不是进程
+        assert_eq!(proc.get_info(h0, InfoTopic::HandleTable).err(), Some(ZxError::WRONG_TYPE));
+    }
+
+    #[test]
+    fn process_handles() {
+        let proc = Process::new();
+        let other = Process::new();
+        let (channel0, channel1) = Channel::create();
+        let hp = proc.add_handle(Handle::with_default_rights(other.clone())).unwrap();
+        let c0 = other.add_handle(Handle::with_default_rights(channel0.clone())).unwrap();
+        let c1 = other.add_handle(Handle::with_default_rights(channel1)).unwrap();
+        let d = other.add_handle(Handle::with_default_rights(DummyObject::new())).unwrap();
+
+        let stats = match proc.get_info(hp, InfoTopic::ProcessHandleStats).unwrap() {
+            ObjectInfo::ProcessHandleStats(stats) => stats,
+            info => panic!("unexpected info {:?}", info),
+        };
+        assert_eq!(stats.handle_count.get(&ObjectType::CHANNEL), Some(&2));
+        assert_eq!(stats.handle_count.get(&ObjectType::NONE), Some(&1));
+
+        let table = match proc.get_info(hp, InfoTopic::HandleTable).unwrap() {
+            ObjectInfo::HandleTable(table) => table,
+            info => panic!("unexpected info {:?}", info),
+        };
+        assert_eq!(table.iter().map(|h| h.handle).collect::<Vec<_>>(), [c0, c1, d]);
+        assert_eq!(table[0].koid, channel0.id());
+        assert_eq!(table[0].related_koid, table[1].koid);
+        assert_eq!(table[2].rights, Rights::BASIC);
+    }
+}
@@ -0,0 +1,190 @@
+use spin::Mutex;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use crate::error::*;
+use crate::object::*;
+use crate::impl_kobject;
+use super::Process;
+
+/// 作业对象，作业是一组进程和子作业的容器，所有作业构成一棵树。
+/// 杀死一个作业会连带杀死它下面的整棵子树。
+pub struct Job {
+    base: KObjectBase,
+    parent: Weak<Job>,      //父作业，父作业持有子作业的强引用，所以这里用弱引用
+    inner: Mutex<JobInner>, //作业对象的可变部分
+}
+impl_kobject!(Job, JOB
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_JOB
+    }
+    fn related_koid(&self) -> KoID {
+        self.parent.upgrade().map(|parent| parent.id()).unwrap_or(0)
+    }
+);
+
+#[derive(Default)]
+struct JobInner {
+    children: Vec<Arc<Job>>,      //子作业
+    processes: Vec<Arc<Process>>, //直接属于这个作业的进程
+    killed: bool,                 //作业是否已经被杀死
+    kill_on_oom: bool,            //内存不足时是否杀死这个作业
+}
+
+impl Job {
+    /// 创建一个根作业
+    pub fn root() -> Arc<Self> {
+        Self::new_with_parent(Weak::new())
+    }
+    fn new_with_parent(parent: Weak<Job>) -> Arc<Self> {
+        Arc::new(Job {
+            base: KObjectBase::default(),
+            parent,
+            inner: Mutex::new(JobInner::default()),
+        })
+    }
+    /// 在这个作业下创建一个子作业，作业已经被杀死时返回 BAD_STATE
+    pub fn create_child(self: &Arc<Self>) -> ZxResult<Arc<Self>> {
+        let mut inner = self.inner.lock();
+        if inner.killed {
+            return Err(ZxError::BAD_STATE);
+        }
+        let child = Self::new_with_parent(Arc::downgrade(self));
+        inner.children.push(child.clone());
+        Ok(child)
+    }
+    /// 获取父作业，根作业返回 None
+    pub fn parent(&self) -> Option<Arc<Job>> {
+        self.parent.upgrade()
+    }
+    /// 将进程加入这个作业，由 Process::create 调用
+    pub(super) fn add_process(&self, proc: Arc<Process>) -> ZxResult<()> {
+        let mut inner = self.inner.lock();
+        if inner.killed {
+            return Err(ZxError::BAD_STATE);
+        }
+        inner.processes.push(proc);
+        Ok(())
+    }
+    /// 将进程从这个作业中移除，由进程退出时调用
+    pub(super) fn remove_process(&self, id: KoID) {
+        self.inner.lock().processes.retain(|p| p.id() != id);
+    }
+    /// 获取所有子作业的 koid
+    pub fn children_ids(&self) -> Vec<KoID> {
+        self.inner.lock().children.iter().map(|j| j.id()).collect()
+    }
+    /// 获取所有直属进程的 koid
+    pub fn process_ids(&self) -> Vec<KoID> {
+        self.inner.lock().processes.iter().map(|p| p.id()).collect()
+    }
+    /// 根据 koid 查找子作业
+    pub fn get_child(&self, id: KoID) -> ZxResult<Arc<Job>> {
+        let inner = self.inner.lock();
+        let child = inner.children.iter().find(|j| j.id() == id);
+        child.cloned().ok_or(ZxError::NOT_FOUND)
+    }
+    /// 根据 koid 查找直属进程
+    pub fn get_process(&self, id: KoID) -> ZxResult<Arc<Process>> {
+        let inner = self.inner.lock();
+        let proc = inner.processes.iter().find(|p| p.id() == id);
+        proc.cloned().ok_or(ZxError::NOT_FOUND)
+    }
+    /// 作业是否已经被杀死
+    pub fn is_killed(&self) -> bool {
+        self.inner.lock().killed
+    }
+    /// 内存不足时是否杀死这个作业
+    pub fn kill_on_oom(&self) -> bool {
+        self.inner.lock().kill_on_oom
+    }
+    /// 设置内存不足时是否杀死这个作业
+    pub(crate) fn set_kill_on_oom(&self, kill: bool) {
+        self.inner.lock().kill_on_oom = kill;
+    }
+    /// 杀死这个作业：递归杀死所有子作业和进程，然后从父作业中移除自己
+    pub fn kill(&self) {
+        let (children, processes) = {
+            let mut inner = self.inner.lock();
+            if inner.killed {
+                return;
+            }
+            inner.killed = true;
+            //和进程退出一样，先把子节点拿出来再放锁，进程退出时还要回来拿作业的锁
+            (
+                core::mem::take(&mut inner.children),
+                core::mem::take(&mut inner.processes),
+            )
+        };
+        for child in children {
+            child.kill();
+        }
+        for proc in processes {
+            proc.kill();
+        }
+        self.base.signal_set(Signal::SIGNALED); //通知等待者：作业已经终止
+        if let Some(parent) = self.parent() {
+            parent.inner.lock().children.retain(|j| j.id() != self.id());
+        }
+    }
+}
+
+#[cfg(test)]
+mod job_test {
+    use super::*;
+    use crate::task::*;
+    use alloc::vec;
+
+    #[test]
+    fn create() {
+        let root = Job::root();
+        assert_eq!(root.type_name(), "Job");
+        assert!(root.parent().is_none());
+
+        let job = root.create_child().unwrap();
+        assert!(Arc::ptr_eq(&job.parent().unwrap(), &root));
+        assert_eq!(root.children_ids(), vec![job.id()]);
+        assert!(Arc::ptr_eq(&root.get_child(job.id()).unwrap(), &job));
+
+        let proc = Process::create(&job, "proc").unwrap();
+        assert_eq!(proc.name(), "proc");
+        assert!(Arc::ptr_eq(&proc.job().unwrap(), &job));
+        assert_eq!(job.process_ids(), vec![proc.id()]);
+        assert!(Arc::ptr_eq(&job.get_process(proc.id()).unwrap(), &proc));
+        assert_eq!(root.get_process(proc.id()).err(), Some(ZxError::NOT_FOUND));
+
+        // 进程退出后从作业中移除
+        proc.exit(0);
+        assert!(job.process_ids().is_empty());
+    }
+
+    #[test]
+    fn kill() {
+        let root = Job::root();
+        let job = root.create_child().unwrap();
+        let child = job.create_child().unwrap();
+        let proc = Process::create(&job, "proc").unwrap();
+        let child_proc = Process::create(&child, "child_proc").unwrap();
+        let thread = Thread::create(&child_proc, "thread").unwrap();
+        let handle_value = child_proc.add_handle(Handle::new(proc.clone(), Rights::DEFAULT_PROCESS)).unwrap();
+
+        job.kill();
+        assert!(job.is_killed());
+        assert!(job.signal().contains(Signal::SIGNALED));
+        assert!(child.is_killed());
+        assert!(!root.is_killed());
+        assert!(root.children_ids().is_empty());
+        assert!(job.children_ids().is_empty());
+        assert_eq!(proc.status(), ProcessStatus::Exited(TASK_RETCODE_SYSCALL_KILL));
+        assert_eq!(child_proc.status(), ProcessStatus::Exited(TASK_RETCODE_SYSCALL_KILL));
+        assert_eq!(thread.state(), ThreadState::Dead);
+        assert_eq!(
+            child_proc.get_object_with_rights::<Process>(handle_value, Rights::DEFAULT_PROCESS).err(),
+            Some(ZxError::BAD_HANDLE)
+        );
+
+        // 被杀死的作业下不能再创建任何东西
+        assert_eq!(job.create_child().err(), Some(ZxError::BAD_STATE));
+        assert_eq!(Process::create(&job, "p").err(), Some(ZxError::BAD_STATE));
+    }
+}
@@ -1,51 +1,230 @@
 use spin::Mutex; //因为不能用依赖操作系统提供系统调用的std,所以用了no_std兼容的spin库中的mutex来实现简单互斥锁。具体实现细节先不深纠，一样用。
-use alloc::collections::BTreeMap;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 
 
 use crate::error::*;
 use crate::object::*; //引入object模块（包括父模块和子模块，因为在父模块中公开引入了所有子模块，所以在这里只要*就可以了）
 use crate::impl_kobject;  //虽然impl_kobject是在object模块下实现的，但#[macro_export] 导出宏到crate根了，所以要从crate里引入。
+use crate::ipc::{Channel, MessagePacket, MAX_MSG_HANDLES};
+use crate::time::Time;
+use crate::vm::Vmar;
+use super::{FutexTable, HandleTable, Job, Thread};
 
 #[allow(dead_code)]
 /// 进程对象
 pub struct Process {
     base: KObjectBase,                 //注意：基类中也有一个inner,里面保存的是基类的可变部分。
+    job: Weak<Job>,                    //进程所属的作业，作业持有进程的强引用，所以这里用弱引用避免循环引用
+    vmar: Arc<Vmar>,                   //进程的根地址区域，覆盖整个用户地址空间
+    futexes: FutexTable,               //进程地址空间中的 futex，按用户地址索引
     inner: Mutex<ProcessInner>,        //这里是进程对象的可变部分
 }
-impl_kobject!(Process);// 宏的作用：补充
+impl_kobject!(Process, PROCESS
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_PROCESS
+    }
+    fn related_koid(&self) -> KoID {
+        self.job.upgrade().map(|job| job.id()).unwrap_or(0)
+    }
+);// 宏的作用：补充
 #[allow(dead_code)]
 struct ProcessInner {
-    handles: BTreeMap<HandleValue, Handle>, //进程对象的内部可变部分是一个用BTreeMap实现的句柄， 用于构建树的key是HandleValue，value就是句柄
+    handles: HandleTable,                   //进程的句柄表，由句柄值找到句柄
+    threads: Vec<Arc<Thread>>,              //进程中的线程列表，线程创建时加入，退出时移除
+    status: ProcessStatus,                  //进程的运行状态
 }
 
 pub type HandleValue = u32; //在这定义一个类型用作键值对中的key
 
+/// 通过通道写入句柄时对句柄的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleOp {
+    /// 把句柄移动给对端，需要 TRANSFER 权限
+    Move,
+    /// 复制一份给对端，原句柄保留，需要 DUPLICATE 权限
+    Duplicate,
+}
+
+/// `channel_write_etc` 中对一个句柄的处理要求
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandleDisposition {
+    /// 处理方式
+    pub op: HandleOp,
+    /// 要写入的句柄
+    pub handle: HandleValue,
+    /// 期望的对象类型，None 表示不检查
+    pub obj_type: Option<ObjectType>,
+    /// 对端收到的句柄的权限，只能是原权限的子集，None 表示保持原权限
+    pub rights: Option<Rights>,
+    /// 这个句柄的处理结果
+    pub result: ZxResult<()>,
+}
+
+impl HandleDisposition {
+    /// 以保持原权限、不检查类型的方式移动句柄 `handle`
+    pub fn new(handle: HandleValue) -> Self {
+        HandleDisposition {
+            op: HandleOp::Move,
+            handle,
+            obj_type: None,
+            rights: None,
+            result: Ok(()),
+        }
+    }
+}
+
+/// `channel_read_etc` 返回的收到的句柄的信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandleInfo {
+    /// 加入本进程之后的句柄值
+    pub handle: HandleValue,
+    /// 句柄指向的对象的类型
+    pub obj_type: ObjectType,
+    /// 句柄的权限
+    pub rights: Rights,
+}
+
+/// 进程的运行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessStatus {
+    /// 正在运行
+    Running,
+    /// 已经退出，携带退出码
+    Exited(i64),
+}
+
+/// 进程被 kill 时的退出码
+pub const TASK_RETCODE_SYSCALL_KILL: i64 = -1024;
+
 impl Process {
-    /// 创建一个新的进程对象
+    /// 创建一个新的进程对象，它不属于任何作业
     pub fn new() -> Arc<Self> {
+        Self::new_with_job(Weak::new())
+    }
+    /// 在作业 `job` 下创建一个新的进程对象，作业已经被杀死时返回 BAD_STATE
+    pub fn create(job: &Arc<Job>, name: &str) -> ZxResult<Arc<Self>> {
+        let proc = Self::new_with_job(Arc::downgrade(job));
+        proc.base.set_name(name);
+        job.add_process(proc.clone())?;
+        Ok(proc)
+    }
+    fn new_with_job(job: Weak<Job>) -> Arc<Self> {
         Arc::new(Process {
             base: KObjectBase::default(),
+            job,
+            vmar: Vmar::new_root(),
+            futexes: FutexTable::default(),
             inner: Mutex::new(ProcessInner {
-                handles: BTreeMap::default(), //创建一个空的B树，或者B+树？不重要，具体实现不追究了，总之是一种键值对的存储方式。
+                handles: HandleTable::default(),
+                threads: Vec::new(),
+                status: ProcessStatus::Running,
             }),
         })
     }
-    ///为调用此函数的进程对象添加一个句柄
-    pub fn add_handle(&self, handle: Handle) -> HandleValue {
-
-        let mut inner = self.inner.lock();  //取得锁
-        //从0开始找一个当前树中没有的索引作为key（handle_value）返回
-        let value = (0 as HandleValue..)   
-            .find(|idx| !inner.handles.contains_key(idx))
-            .unwrap();
-        // 插入BTreeMap
-        inner.handles.insert(value, handle);
-        value
+    /// 获取进程所属的作业，孤儿进程或作业已销毁时返回 None
+    pub fn job(&self) -> Option<Arc<Job>> {
+        self.job.upgrade()
+    }
+    /// 获取进程的根地址区域
+    pub fn vmar(&self) -> Arc<Vmar> {
+        self.vmar.clone()
+    }
+    /// 获取进程当前的状态
+    pub fn status(&self) -> ProcessStatus {
+        self.inner.lock().status
+    }
+    /// 进程退出：杀死所有线程，关闭所有句柄，并从所属作业中移除
+    pub fn exit(&self, retcode: i64) {
+        let (threads, handles) = {
+            let mut inner = self.inner.lock();
+            if let ProcessStatus::Exited(_) = inner.status {
+                return;
+            }
+            inner.status = ProcessStatus::Exited(retcode);
+            //把线程和句柄都拿出来，放掉锁之后再处理，因为线程退出时还要回来拿进程的锁
+            (
+                core::mem::take(&mut inner.threads),
+                core::mem::take(&mut inner.handles),
+            )
+        };
+        for thread in threads {
+            thread.kill();
+        }
+        drop(handles); //句柄被丢弃，也就关闭了它们对内核对象的引用
+        self.vmar.destroy(); //回收整个地址空间
+        self.base.signal_set(Signal::SIGNALED); //通知等待者：进程已经终止
+        if let Some(job) = self.job() {
+            job.remove_process(self.id());
+        }
+    }
+    /// 杀死进程
+    pub fn kill(&self) {
+        self.exit(TASK_RETCODE_SYSCALL_KILL);
+    }
+    ///为调用此函数的进程对象添加一个句柄，句柄数达到上限时返回 NO_RESOURCES
+    pub fn add_handle(&self, handle: Handle) -> ZxResult<HandleValue> {
+        self.inner.lock().handles.add(handle)
     }
-    ///传入作为key的handlevalue,删除对应句柄
-    pub fn remove_handle(&self, handle_value: HandleValue) {
-        self.inner.lock().handles.remove(&handle_value);
+    ///传入句柄值，删除对应句柄，句柄值无效时返回 BAD_HANDLE
+    pub fn remove_handle(&self, handle_value: HandleValue) -> ZxResult<()> {
+        self.inner.lock().handles.remove(handle_value).map(|_| ())
+    }
+    ///复制句柄 `handle_value`，新句柄的权限为 `rights`，返回新的句柄值。
+    ///原句柄需要 DUPLICATE 权限；`rights` 只能是原权限的子集，SAME_RIGHTS 表示保持原权限。
+    pub fn handle_duplicate(&self, handle_value: HandleValue, rights: Rights) -> ZxResult<HandleValue> {
+        let mut inner = self.inner.lock();
+        let handle = inner.handles.get(handle_value)?;
+        if !handle.rights.contains(Rights::DUPLICATE) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let new_handle = Self::attenuate(handle, rights)?;
+        inner.handles.add(new_handle)
+    }
+    ///用权限为 `rights` 的新句柄替换句柄 `handle_value`，原句柄失效，返回新的句柄值。
+    ///和 `handle_duplicate` 一样要求原句柄有 DUPLICATE 权限；失败时原句柄保持不变。
+    pub fn handle_replace(&self, handle_value: HandleValue, rights: Rights) -> ZxResult<HandleValue> {
+        let mut inner = self.inner.lock();
+        let handle = inner.handles.get(handle_value)?;
+        if !handle.rights.contains(Rights::DUPLICATE) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let new_handle = Self::attenuate(handle, rights)?;
+        inner.handles.remove(handle_value)?;
+        inner.handles.add(new_handle)
+    }
+    ///根据 `handle` 生成一个权限为 `rights` 的句柄，只能削减权限
+    fn attenuate(handle: &Handle, rights: Rights) -> ZxResult<Handle> {
+        let rights = if rights == Rights::SAME_RIGHTS {
+            handle.rights
+        } else if handle.rights.contains(rights) {
+            rights
+        } else {
+            return Err(ZxError::INVALID_ARGS);
+        };
+        Ok(Handle::new(handle.object.clone(), rights))
+    }
+    ///关闭一组句柄，其中无效的句柄被跳过，只要有一个无效就返回 BAD_HANDLE
+    pub fn handle_close_many(&self, handle_values: &[HandleValue]) -> ZxResult<()> {
+        let mut inner = self.inner.lock();
+        let mut result = Ok(());
+        for value in handle_values {
+            if inner.handles.remove(*value).is_err() {
+                result = Err(ZxError::BAD_HANDLE);
+            }
+        }
+        result
+    }
+    ///检查句柄 `handle_value` 是否有效，无效时返回 BAD_HANDLE
+    pub fn handle_check_valid(&self, handle_value: HandleValue) -> ZxResult<()> {
+        match self.inner.lock().handles.contains(handle_value) {
+            true => Ok(()),
+            false => Err(ZxError::BAD_HANDLE),
+        }
+    }
+    ///在持有锁的情况下访问整张句柄表
+    pub(super) fn with_handles<R>(&self, f: impl FnOnce(&HandleTable) -> R) -> R {
+        f(&self.inner.lock().handles)
     }
     /// 根据句柄值查找内核对象，并检查权限
     pub fn get_object_with_rights<T: KernelObject>(
@@ -57,12 +236,12 @@ impl Process {
             .inner
             .lock()     //取锁以访问句柄树
             .handles    
-            .get(&handle_value)          //根据key在process对象的句柄树中查找对应的句柄对象
-            .ok_or(ZxError::BAD_HANDLE)?        //如果找不到对应的句柄，函数将返回一个 ZxError::BAD_HANDLE 错误
+            .get(handle_value)?          //根据句柄值在process对象的句柄表中查找对应的句柄对象，找不到时返回 BAD_HANDLE
             .clone();            //使用 .clone() 方法克隆句柄对象，以便在不改变原始集合的情况下使用句柄。
         // check type before rights
         let object = handle          //利用这个句柄对象
             .object            //找到它对应的抽象对象
+            .clone()
             .downcast_arc::<T>()                 //向下转换成被Arc包裹的具体类型对象
             .map_err(|_| ZxError::WRONG_TYPE)?;  //闭包 |_| ZxError::WRONG_TYPE 表示不管原始错误是什么，都将其转换为 ZxError::WRONG_TYPE
         if !handle.rights.contains(desired_rights) { //如果这个句柄不包含应有的权限
@@ -70,6 +249,206 @@ impl Process {
         }
         Ok(object) //一切正常后，返回一个对“要查找对象”的Arc克隆。
     }
+    ///通过通道 `handle_value` 发送数据 `data`，同时把句柄 `handles` 从本进程转移给对端。
+    ///每个句柄都必须有 TRANSFER 权限，并且不能是通道自己；只有写入成功时句柄才会从本进程中移除，
+    ///失败时本进程的句柄表保持不变。
+    pub fn channel_write(
+        &self,
+        handle_value: HandleValue,
+        data: &[u8],
+        handles: &[HandleValue],
+    ) -> ZxResult<()> {
+        let mut dispositions: Vec<_> = handles.iter().map(|&h| HandleDisposition::new(h)).collect();
+        self.channel_write_etc(handle_value, data, &mut dispositions)
+    }
+    ///和 `channel_write` 相同，但每个句柄都按照各自的 `HandleDisposition` 处理：
+    ///可以移动或复制，可以检查对象类型，可以削减权限。每个句柄的处理结果写回 `result`，
+    ///任何一个失败时整个写入失败，返回第一个错误，本进程的句柄表保持不变。
+    ///要移动的句柄在列表中出现了不止一次（移动两次，或者既移动又复制）时，这些项都返回 INVALID_ARGS。
+    pub fn channel_write_etc(
+        &self,
+        handle_value: HandleValue,
+        data: &[u8],
+        dispositions: &mut [HandleDisposition],
+    ) -> ZxResult<()> {
+        let channel = self.get_object_with_rights::<Channel>(handle_value, Rights::WRITE)?;
+        let mut msg = MessagePacket {
+            data: data.to_vec(),
+            handles: Vec::with_capacity(dispositions.len()),
+        };
+        //检查和取出在同一把锁下完成，保证句柄不会在这期间被别人拿走
+        let mut inner = self.inner.lock();
+        let mut first_err = None;
+        for i in 0..dispositions.len() {
+            let d = &dispositions[i];
+            let conflict = dispositions.iter().enumerate().any(|(j, other)| {
+                j != i && other.handle == d.handle && (d.op == HandleOp::Move || other.op == HandleOp::Move)
+            });
+            let result = if conflict {
+                Err(ZxError::INVALID_ARGS)
+            } else {
+                Self::dispose_handle(&inner.handles, &channel, d)
+            };
+            dispositions[i].result = match result {
+                Ok(handle) => {
+                    msg.handles.push(handle);
+                    Ok(())
+                }
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                    Err(err)
+                }
+            };
+        }
+        if let Some(err) = first_err {
+            return Err(err);
+        }
+        //要移动的句柄先取出来，句柄值马上失效，但槽位保留着，写入失败时可以原样放回
+        let taken: Vec<(HandleValue, Handle)> = dispositions
+            .iter()
+            .filter(|d| d.op == HandleOp::Move)
+            .map(|d| (d.handle, inner.handles.take(d.handle).unwrap())) //上面在同一把锁下检查过，一定存在
+            .collect();
+        //写入会修改通道的信号并调用回调，回调里可能访问这个进程的句柄，所以不能拿着进程的锁写
+        drop(inner);
+        let result = channel.write(msg);
+        let mut inner = self.inner.lock();
+        for (value, handle) in taken {
+            match result {
+                Ok(()) => inner.handles.release(value),
+                Err(_) => inner.handles.put_back(value, handle),
+            }
+        }
+        drop(inner);
+        if let Err(err) = result {
+            //消息没有写进去，句柄一个也没有转移出去，每一项都报告写入的错误
+            for d in dispositions.iter_mut() {
+                d.result = Err(err);
+            }
+        }
+        result
+    }
+    ///按照 `disposition` 生成要写入消息的句柄
+    fn dispose_handle(
+        handles: &HandleTable,
+        channel: &Arc<Channel>,
+        disposition: &HandleDisposition,
+    ) -> ZxResult<Handle> {
+        let handle = handles.get(disposition.handle)?;
+        if handle.object.id() == channel.id() {
+            return Err(ZxError::NOT_SUPPORTED); //不能把通道自己通过自己发出去
+        }
+        let required = match disposition.op {
+            HandleOp::Move => Rights::TRANSFER,
+            HandleOp::Duplicate => Rights::DUPLICATE,
+        };
+        if !handle.rights.contains(required) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        if let Some(obj_type) = disposition.obj_type {
+            if handle.object.obj_type() != obj_type {
+                return Err(ZxError::WRONG_TYPE);
+            }
+        }
+        let rights = match disposition.rights {
+            Some(rights) if !handle.rights.contains(rights) => return Err(ZxError::INVALID_ARGS), //只能削减权限
+            Some(rights) => rights,
+            None => handle.rights,
+        };
+        Ok(Handle::new(handle.object.clone(), rights))
+    }
+    ///从通道 `handle_value` 读取一条消息，消息中的句柄被加入本进程的句柄表，返回数据和新的句柄值
+    pub fn channel_read(&self, handle_value: HandleValue) -> ZxResult<(Vec<u8>, Vec<HandleValue>)> {
+        let (data, infos) = self.channel_read_etc(handle_value)?;
+        Ok((data, infos.into_iter().map(|info| info.handle).collect()))
+    }
+    ///和 `channel_read` 相同，但对每个收到的句柄还返回它指向的对象类型和权限
+    pub fn channel_read_etc(&self, handle_value: HandleValue) -> ZxResult<(Vec<u8>, Vec<HandleInfo>)> {
+        let channel = self.get_object_with_rights::<Channel>(handle_value, Rights::READ)?;
+        //读取会修改通道的信号并调用回调，不能拿着进程的锁读。
+        //先在句柄表中为消息里的句柄预留位置，读完再回来把句柄放进去，这期间别人占不走这些位置
+        let reserved = self.inner.lock().handles.reserve(MAX_MSG_HANDLES);
+        //句柄表放不下时消息留在通道里，句柄和数据都不会丢，腾出空间之后可以再读
+        let result = channel.read_if(|msg| {
+            if msg.handles.len() > reserved {
+                return Err(ZxError::NO_RESOURCES);
+            }
+            Ok(())
+        });
+        let mut inner = self.inner.lock();
+        let msg = match result {
+            Ok(msg) => msg,
+            Err(err) => {
+                inner.handles.unreserve(reserved);
+                return Err(err);
+            }
+        };
+        inner.handles.unreserve(reserved - msg.handles.len());
+        let infos = msg
+            .handles
+            .into_iter()
+            .map(|handle| HandleInfo {
+                obj_type: handle.object.obj_type(),
+                rights: handle.rights,
+                handle: inner.handles.add_reserved(handle),
+            })
+            .collect();
+        Ok((msg.data, infos))
+    }
+    ///将线程加入进程的线程列表，由 Thread::create 调用，进程已经退出时返回 BAD_STATE
+    pub(super) fn add_thread(&self, thread: Arc<Thread>) -> ZxResult<()> {
+        let mut inner = self.inner.lock();
+        if inner.status != ProcessStatus::Running {
+            return Err(ZxError::BAD_STATE);
+        }
+        inner.threads.push(thread);
+        Ok(())
+    }
+    ///将线程从进程的线程列表中移除，由线程退出时调用
+    pub(super) fn remove_thread(&self, tid: KoID) {
+        self.inner.lock().threads.retain(|t| t.id() != tid);
+    }
+    ///获取进程中所有线程的 koid
+    pub fn thread_ids(&self) -> Vec<KoID> {
+        self.inner.lock().threads.iter().map(|t| t.id()).collect()
+    }
+    /// 如果用户地址 `addr` 处的值等于 `expected`，线程 `thread` 就阻塞在这个 futex 上，
+    /// 直到被唤醒或到达 `deadline`；`new_owner` 是这个 futex 新的持有者
+    pub fn futex_wait(
+        &self,
+        thread: &Arc<Thread>,
+        addr: usize,
+        expected: i32,
+        new_owner: Option<Arc<Thread>>,
+        deadline: Time,
+    ) -> ZxResult<()> {
+        self.futexes.wait(self, thread, addr, expected, new_owner, deadline)
+    }
+    /// 唤醒用户地址 `addr` 上最多 `count` 个等待的线程
+    pub fn futex_wake(&self, addr: usize, count: usize) -> ZxResult<()> {
+        self.futexes.wake(addr, count)
+    }
+    /// 唤醒 `addr` 上最多 `wake_count` 个线程，再把最多 `requeue_count` 个线程转移到 `requeue_addr` 上等待
+    #[allow(clippy::too_many_arguments)]
+    pub fn futex_requeue(
+        &self,
+        addr: usize,
+        wake_count: usize,
+        expected: i32,
+        requeue_addr: usize,
+        requeue_count: usize,
+        requeue_owner: Option<Arc<Thread>>,
+    ) -> ZxResult<()> {
+        self.futexes.requeue(self, addr, wake_count, expected, requeue_addr, requeue_count, requeue_owner)
+    }
+    /// 获取用户地址 `addr` 上 futex 的持有者
+    pub fn futex_owner(&self, addr: usize) -> Option<Arc<Thread>> {
+        self.futexes.owner(addr)
+    }
+    /// 用户地址 `addr` 上正在等待的线程数
+    pub fn futex_waiter_count(&self, addr: usize) -> usize {
+        self.futexes.waiter_count(addr)
+    }
 }
 
 
@@ -83,8 +462,9 @@ mod process_test{
     fn new_proc() {
         let proc = Process::new();
         assert_eq!(proc.type_name(), "Process");
+        assert_eq!(proc.obj_type(), ObjectType::PROCESS);
         assert_eq!(proc.name(), "");
-        proc.set_name("proc1");
+        proc.base.set_name("proc1");
         assert_eq!(proc.name(), "proc1");
         assert_eq!(
             format!("{:?}", proc),
@@ -94,7 +474,7 @@ mod process_test{
         let obj: Arc<dyn KernelObject> = proc;
         assert_eq!(obj.type_name(), "Process");
         assert_eq!(obj.name(), "proc1");
-        obj.set_name("proc2");
+        obj.base().set_name("proc2");
         assert_eq!(obj.name(), "proc2");
         assert_eq!(
             format!("{:?}", obj),
@@ -105,7 +485,7 @@ mod process_test{
     fn proc_handle() {
         let proc = Process::new();
         let handle = Handle::new(proc.clone(), Rights::DEFAULT_PROCESS); //创建一个包含”默认进程“权限，连接到proc对象的句柄
-        let handle_value = proc.add_handle(handle); //将句柄授予进程，并用handle_value保存此句柄的key
+        let handle_value = proc.add_handle(handle).unwrap(); //将句柄授予进程，并用handle_value保存此句柄的key
         //这里利用key找到handle句柄，并检查其权限，最后返回proc对象，让object1共享其所有权。
         let object1: Arc<Process> = proc 
             .get_object_with_rights(handle_value, Rights::DEFAULT_PROCESS)
@@ -113,6 +493,242 @@ mod process_test{
         //断言他们由一个arc所管理，指向同样的实例
         assert!(Arc::ptr_eq(&object1, &proc));
 
-        proc.remove_handle(handle_value);
+        proc.remove_handle(handle_value).unwrap();
+        assert_eq!(proc.remove_handle(handle_value), Err(ZxError::BAD_HANDLE));
+    }
+    #[test]
+    fn exit() {
+        let proc = Process::new();
+        let thread = Thread::create(&proc, "thread").unwrap();
+        let handle_value = proc.add_handle(Handle::new(proc.clone(), Rights::DEFAULT_PROCESS)).unwrap();
+        proc.kill();
+        assert_eq!(proc.status(), ProcessStatus::Exited(TASK_RETCODE_SYSCALL_KILL));
+        assert_eq!(thread.state(), crate::task::ThreadState::Dead);
+        assert!(proc.thread_ids().is_empty());
+        assert!(proc.signal().contains(Signal::SIGNALED));
+        assert!(proc.vmar().is_dead());
+        //句柄已经全部关闭
+        assert_eq!(
+            proc.get_object_with_rights::<Process>(handle_value, Rights::DEFAULT_PROCESS).err(),
+            Some(ZxError::BAD_HANDLE)
+        );
+        //退出的进程不能再创建线程
+        assert_eq!(Thread::create(&proc, "t").err(), Some(ZxError::BAD_STATE));
+    }
+    #[test]
+    fn channel_transfer() {
+        let rights = Rights::READ | Rights::WRITE | Rights::TRANSFER;
+        let (proc0, proc1) = (Process::new(), Process::new());
+        let (channel0, channel1) = Channel::create();
+        let ch0 = proc0.add_handle(Handle::new(channel0, rights)).unwrap();
+        let ch1 = proc1.add_handle(Handle::new(channel1, rights)).unwrap();
+        let object = DummyObject::new();
+        let movable = proc0.add_handle(Handle::new(object.clone(), Rights::TRANSFER)).unwrap();
+        let pinned = proc0.add_handle(Handle::new(object.clone(), Rights::DUPLICATE)).unwrap();
+
+        //失败时句柄都还留在发送方
+        assert_eq!(proc0.channel_write(ch0, b"x", &[movable, pinned]).err(), Some(ZxError::ACCESS_DENIED));
+        assert_eq!(proc0.channel_write(ch0, b"x", &[movable, movable]).err(), Some(ZxError::INVALID_ARGS));
+        assert_eq!(proc0.channel_write(ch0, b"x", &[ch0]).err(), Some(ZxError::NOT_SUPPORTED));
+        assert_eq!(proc0.channel_write(ch0, b"x", &[99]).err(), Some(ZxError::BAD_HANDLE));
+        assert!(proc0.get_object_with_rights::<DummyObject>(movable, Rights::TRANSFER).is_ok());
+        assert_eq!(proc1.channel_read(ch1).err(), Some(ZxError::SHOULD_WAIT));
+
+        //成功后句柄从发送方移动到接收方
+        proc0.channel_write(ch0, b"hello", &[movable]).unwrap();
+        assert_eq!(
+            proc0.get_object_with_rights::<DummyObject>(movable, Rights::empty()).err(),
+            Some(ZxError::BAD_HANDLE)
+        );
+        let (data, handles) = proc1.channel_read(ch1).unwrap();
+        assert_eq!(data, b"hello");
+        assert_eq!(handles.len(), 1);
+        let received: Arc<DummyObject> =
+            proc1.get_object_with_rights(handles[0], Rights::TRANSFER).unwrap();
+        assert!(Arc::ptr_eq(&received, &object));
+    }
+    #[test]
+    fn channel_write_etc() {
+        let rights = Rights::READ | Rights::WRITE | Rights::TRANSFER | Rights::DUPLICATE;
+        let (proc0, proc1) = (Process::new(), Process::new());
+        let (channel0, channel1) = Channel::create();
+        let ch0 = proc0.add_handle(Handle::new(channel0, rights)).unwrap();
+        let ch1 = proc1.add_handle(Handle::new(channel1, rights)).unwrap();
+        let object = DummyObject::new();
+        let h0 = proc0.add_handle(Handle::new(object.clone(), rights)).unwrap();
+        let h1 = proc0.add_handle(Handle::new(object.clone(), Rights::TRANSFER)).unwrap();
+        let h2 = proc0.add_handle(Handle::new(object, Rights::TRANSFER)).unwrap();
+
+        //类型不对、权限不能放大，失败时逐个报告结果，句柄都还在
+        let mut dispositions = [
+            HandleDisposition { obj_type: Some(ObjectType::CHANNEL), ..HandleDisposition::new(h0) },
+            HandleDisposition { rights: Some(rights), ..HandleDisposition::new(h1) },
+            HandleDisposition { op: HandleOp::Duplicate, ..HandleDisposition::new(h2) },
+        ];
+        assert_eq!(
+            proc0.channel_write_etc(ch0, b"", &mut dispositions).err(),
+            Some(ZxError::WRONG_TYPE)
+        );
+        assert_eq!(dispositions[0].result, Err(ZxError::WRONG_TYPE));
+        assert_eq!(dispositions[1].result, Err(ZxError::INVALID_ARGS));
+        assert_eq!(dispositions[2].result, Err(ZxError::ACCESS_DENIED));
+
+        //复制一份削减了权限的句柄，再移动另一个句柄
+        let mut dispositions = [
+            HandleDisposition {
+                op: HandleOp::Duplicate,
+                obj_type: Some(ObjectType::NONE),
+                rights: Some(Rights::READ),
+                ..HandleDisposition::new(h0)
+            },
+            HandleDisposition::new(h1),
+        ];
+        proc0.channel_write_etc(ch0, b"etc", &mut dispositions).unwrap();
+        assert!(dispositions.iter().all(|d| d.result.is_ok()));
+        assert!(proc0.get_object_with_rights::<DummyObject>(h0, rights).is_ok());
+        assert_eq!(
+            proc0.get_object_with_rights::<DummyObject>(h1, Rights::empty()).err(),
+            Some(ZxError::BAD_HANDLE)
+        );
+
+        let (data, infos) = proc1.channel_read_etc(ch1).unwrap();
+        assert_eq!(data, b"etc");
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].obj_type, ObjectType::NONE);
+        assert_eq!(infos[0].rights, Rights::READ);
+        assert_eq!(infos[1].rights, Rights::TRANSFER);
+        assert!(proc1.get_object_with_rights::<DummyObject>(infos[1].handle, Rights::TRANSFER).is_ok());
+
+        //对端关闭了，写入失败，每一项的结果都是写入的错误，句柄留在原处
+        proc1.remove_handle(ch1).unwrap();
+        let mut dispositions = [HandleDisposition::new(h0)];
+        assert_eq!(
+            proc0.channel_write_etc(ch0, b"", &mut dispositions).err(),
+            Some(ZxError::PEER_CLOSED)
+        );
+        assert_eq!(dispositions[0].result, Err(ZxError::PEER_CLOSED));
+        assert!(proc0.get_object_with_rights::<DummyObject>(h0, rights).is_ok());
+    }
+    #[test]
+    fn channel_duplicate_entries() {
+        let rights = Rights::READ | Rights::WRITE | Rights::TRANSFER | Rights::DUPLICATE;
+        let proc = Process::new();
+        let (channel0, channel1) = Channel::create();
+        let ch0 = proc.add_handle(Handle::new(channel0, rights)).unwrap();
+        let h = proc.add_handle(Handle::new(DummyObject::new(), rights)).unwrap();
+
+        //同一个句柄既移动又复制，不管顺序如何都返回 INVALID_ARGS，句柄留在原处
+        let duplicate = HandleDisposition { op: HandleOp::Duplicate, ..HandleDisposition::new(h) };
+        for mut dispositions in [
+            [HandleDisposition::new(h), duplicate.clone()],
+            [duplicate.clone(), HandleDisposition::new(h)],
+        ] {
+            assert_eq!(
+                proc.channel_write_etc(ch0, b"", &mut dispositions).err(),
+                Some(ZxError::INVALID_ARGS)
+            );
+            assert!(dispositions.iter().all(|d| d.result == Err(ZxError::INVALID_ARGS)));
+            assert!(proc.handle_check_valid(h).is_ok());
+        }
+        //复制两次是可以的
+        let mut dispositions = [duplicate.clone(), duplicate];
+        proc.channel_write_etc(ch0, b"", &mut dispositions).unwrap();
+        assert_eq!(channel1.read().unwrap().handles.len(), 2);
+    }
+    #[test]
+    fn channel_callback_uses_handles() {
+        use alloc::boxed::Box;
+        let rights = Rights::READ | Rights::WRITE | Rights::DUPLICATE;
+        let proc = Process::new();
+        let (channel0, channel1) = Channel::create();
+        let ch0 = proc.add_handle(Handle::new(channel0, rights)).unwrap();
+        let ch1 = proc.add_handle(Handle::new(channel1.clone(), rights)).unwrap();
+        let h = proc.add_handle(Handle::new(DummyObject::new(), rights)).unwrap();
+
+        //通道的信号每变一次，回调就复制一次句柄；读写时还拿着进程的锁的话，这里会死锁
+        let weak = Arc::downgrade(&proc);
+        channel1.add_signal_callback(Box::new(move |_| {
+            if let Some(proc) = weak.upgrade() {
+                proc.handle_duplicate(h, Rights::SAME_RIGHTS).unwrap();
+            }
+            false
+        }));
+        let count = || proc.with_handles(|handles| handles.len());
+        assert_eq!(count(), 4);
+        proc.channel_write(ch0, b"x", &[]).unwrap();
+        assert_eq!(count(), 5);
+        assert_eq!(proc.channel_read(ch1).unwrap().0, b"x");
+        assert_eq!(count(), 6);
+    }
+    #[test]
+    fn channel_read_no_room() {
+        let rights = Rights::READ | Rights::WRITE | Rights::TRANSFER;
+        let (proc0, proc1) = (Process::new(), Process::new());
+        let (channel0, channel1) = Channel::create();
+        let ch0 = proc0.add_handle(Handle::new(channel0, rights)).unwrap();
+        proc1.inner.lock().handles = HandleTable::with_limit(2);
+        let ch1 = proc1.add_handle(Handle::new(channel1.clone(), rights)).unwrap();
+        let object = DummyObject::new();
+        let h0 = proc0.add_handle(Handle::new(object.clone(), rights)).unwrap();
+        let h1 = proc0.add_handle(Handle::new(object, rights)).unwrap();
+        proc0.channel_write(ch0, b"two", &[h0, h1]).unwrap();
+
+        //放不下两个句柄，消息留在通道里
+        assert_eq!(proc1.channel_read(ch1).err(), Some(ZxError::NO_RESOURCES));
+        proc1.inner.lock().handles = HandleTable::with_limit(3);
+        let ch1 = proc1.add_handle(Handle::new(channel1, rights)).unwrap();
+        let (data, handles) = proc1.channel_read(ch1).unwrap();
+        assert_eq!(data, b"two");
+        assert_eq!(handles.len(), 2);
+    }
+    #[test]
+    fn duplicate_replace() {
+        let proc = Process::new();
+        let object = DummyObject::new();
+        let rights = Rights::DUPLICATE | Rights::READ | Rights::WRITE;
+        let h0 = proc.add_handle(Handle::new(object.clone(), rights)).unwrap();
+
+        //复制时可以保持或削减权限，不能放大
+        let h1 = proc.handle_duplicate(h0, Rights::SAME_RIGHTS).unwrap();
+        assert!(proc.get_object_with_rights::<DummyObject>(h1, rights).is_ok());
+        let h2 = proc.handle_duplicate(h0, Rights::READ).unwrap();
+        assert_eq!(proc.handle_duplicate(h0, Rights::TRANSFER).err(), Some(ZxError::INVALID_ARGS));
+        //没有 DUPLICATE 权限的句柄不能复制
+        assert_eq!(proc.handle_duplicate(h2, Rights::READ).err(), Some(ZxError::ACCESS_DENIED));
+        assert_eq!(proc.handle_duplicate(99, Rights::READ).err(), Some(ZxError::BAD_HANDLE));
+
+        //替换同样需要 DUPLICATE 权限，之后原句柄失效，失败时原句柄还在
+        assert_eq!(proc.handle_replace(h2, Rights::READ).err(), Some(ZxError::ACCESS_DENIED));
+        assert_eq!(proc.handle_replace(h1, Rights::TRANSFER).err(), Some(ZxError::INVALID_ARGS));
+        assert_eq!(proc.handle_check_valid(h2), Ok(()));
+        assert_eq!(proc.handle_check_valid(h1), Ok(()));
+        let h3 = proc.handle_replace(h1, Rights::WRITE).unwrap();
+        assert_eq!(proc.handle_check_valid(h1), Err(ZxError::BAD_HANDLE));
+        let object3: Arc<DummyObject> = proc.get_object_with_rights(h3, Rights::WRITE).unwrap();
+        assert!(Arc::ptr_eq(&object3, &object));
+        assert_eq!(
+            proc.get_object_with_rights::<DummyObject>(h3, Rights::READ).err(),
+            Some(ZxError::ACCESS_DENIED)
+        );
+
+        //批量关闭，无效的句柄被跳过
+        assert_eq!(proc.handle_close_many(&[h0, h1, h3]), Err(ZxError::BAD_HANDLE));
+        assert_eq!(proc.handle_check_valid(h0), Err(ZxError::BAD_HANDLE));
+        assert_eq!(proc.handle_check_valid(h3), Err(ZxError::BAD_HANDLE));
+        assert_eq!(proc.handle_close_many(&[h2]), Ok(()));
+    }
+    #[test]
+    fn default_rights() {
+        let proc = Process::new();
+        let handle = Handle::with_default_rights(proc.clone());
+        assert_eq!(handle.rights, Rights::DEFAULT_PROCESS);
+        assert!(handle.rights.contains(Rights::BASIC | Rights::IO | Rights::DUPLICATE));
+        assert_ne!(Rights::DEFAULT_PROCESS, Rights::BASIC);
+        //通道默认不能复制
+        let (channel, _) = Channel::create();
+        let handle = Handle::with_default_rights(channel);
+        assert_eq!(handle.rights, Rights::DEFAULT_CHANNEL);
+        assert!(!handle.rights.contains(Rights::DUPLICATE));
+        assert_eq!(Handle::with_default_rights(proc.vmar()).rights, Rights::DEFAULT_VMAR);
     }
 }
@@ -0,0 +1,162 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+
+use crate::error::*;
+use crate::ipc::Socket;
+use crate::object::*;
+use super::{HandleValue, Job, Process, ProcessStatus};
+
+/// 对象属性的编号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyId {
+    /// 对象名，所有对象都有
+    Name,
+    /// 进程的退出码，只读，进程还没有退出时返回 BAD_STATE
+    ProcessExitCode,
+    /// 内存不足时是否杀死作业
+    JobKillOnOom,
+    /// socket 的读阈值
+    SocketRxThreshold,
+    /// socket 的写阈值
+    SocketTxThreshold,
+}
+
+/// 带类型的属性值，和 `PropertyId` 一一对应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Property {
+    Name(String),
+    ProcessExitCode(i64),
+    JobKillOnOom(bool),
+    SocketRxThreshold(usize),
+    SocketTxThreshold(usize),
+}
+
+impl Property {
+    /// 属性值对应的编号
+    pub fn id(&self) -> PropertyId {
+        match self {
+            Property::Name(_) => PropertyId::Name,
+            Property::ProcessExitCode(_) => PropertyId::ProcessExitCode,
+            Property::JobKillOnOom(_) => PropertyId::JobKillOnOom,
+            Property::SocketRxThreshold(_) => PropertyId::SocketRxThreshold,
+            Property::SocketTxThreshold(_) => PropertyId::SocketTxThreshold,
+        }
+    }
+}
+
+impl Process {
+    /// 读取句柄 `handle_value` 指向的对象的属性 `id`，需要 GET_PROPERTY 权限。
+    /// 对象没有这个属性时返回 WRONG_TYPE。
+    pub fn get_property(&self, handle_value: HandleValue, id: PropertyId) -> ZxResult<Property> {
+        let object = self.property_object(handle_value, Rights::GET_PROPERTY)?;
+        let property = match id {
+            PropertyId::Name => Property::Name(object.name()),
+            PropertyId::ProcessExitCode => match downcast::<Process>(&object)?.status() {
+                ProcessStatus::Exited(code) => Property::ProcessExitCode(code),
+                ProcessStatus::Running => return Err(ZxError::BAD_STATE),
+            },
+            PropertyId::JobKillOnOom => Property::JobKillOnOom(downcast::<Job>(&object)?.kill_on_oom()),
+            PropertyId::SocketRxThreshold => {
+                Property::SocketRxThreshold(downcast::<Socket>(&object)?.rx_threshold())
+            }
+            PropertyId::SocketTxThreshold => {
+                Property::SocketTxThreshold(downcast::<Socket>(&object)?.tx_threshold())
+            }
+        };
+        Ok(property)
+    }
+    /// 设置句柄 `handle_value` 指向的对象的属性，需要 SET_PROPERTY 权限。
+    /// 名字超过 MAX_NAME_LEN - 1 字节时在 UTF-8 字符边界处截断；只读属性返回 NOT_SUPPORTED。
+    pub fn set_property(&self, handle_value: HandleValue, property: Property) -> ZxResult<()> {
+        let object = self.property_object(handle_value, Rights::SET_PROPERTY)?;
+        match property {
+            Property::Name(name) => object.base().set_name(&name),
+            Property::ProcessExitCode(_) => {
+                downcast::<Process>(&object)?;
+                return Err(ZxError::NOT_SUPPORTED);
+            }
+            Property::JobKillOnOom(kill) => downcast::<Job>(&object)?.set_kill_on_oom(kill),
+            Property::SocketRxThreshold(n) => downcast::<Socket>(&object)?.set_rx_threshold(n)?,
+            Property::SocketTxThreshold(n) => downcast::<Socket>(&object)?.set_tx_threshold(n)?,
+        }
+        Ok(())
+    }
+    /// 取出句柄指向的对象，并检查权限
+    fn property_object(&self, handle_value: HandleValue, rights: Rights) -> ZxResult<Arc<dyn KernelObject>> {
+        let (object, handle_rights) = self.with_handles(|handles| {
+            handles.get(handle_value).map(|h| (h.object.clone(), h.rights))
+        })?;
+        if !handle_rights.contains(rights) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        Ok(object)
+    }
+}
+
+/// 属性只属于特定类型的对象，类型不对时返回 WRONG_TYPE
+fn downcast<T: KernelObject>(object: &Arc<dyn KernelObject>) -> ZxResult<Arc<T>> {
+    object.clone().downcast_arc::<T>().map_err(|_| ZxError::WRONG_TYPE)
+}
+
+#[cfg(test)]
+mod property_test {
+    use super::*;
+    use crate::ipc::SocketMode;
+
+    #[test]
+    fn name() {
+        let proc = Process::new();
+        let object = DummyObject::new();
+        let h = proc.add_handle(Handle::new(object.clone(), Rights::PROPERTY)).unwrap();
+        proc.set_property(h, Property::Name(String::from("dummy"))).unwrap();
+        assert_eq!(proc.get_property(h, PropertyId::Name), Ok(Property::Name(String::from("dummy"))));
+
+        // 超长的名字被截断到 31 字节，并且不会截断在一个字符的中间
+        let long = "零一二三四五六七八九十"; //每个字符 3 个字节
+        proc.set_property(h, Property::Name(String::from(long))).unwrap();
+        assert_eq!(object.name(), "零一二三四五六七八九");
+        object.base().set_name("0123456789012345678901234567890123456789");
+        assert_eq!(object.name().len(), MAX_NAME_LEN - 1);
+
+        // 没有权限
+        let h = proc.add_handle(Handle::new(object, Rights::GET_PROPERTY)).unwrap();
+        assert!(proc.get_property(h, PropertyId::Name).is_ok());
+        assert_eq!(
+            proc.set_property(h, Property::Name(String::new())).err(),
+            Some(ZxError::ACCESS_DENIED)
+        );
+        assert_eq!(proc.get_property(h, PropertyId::JobKillOnOom).err(), Some(ZxError::WRONG_TYPE));
+    }
+
+    #[test]
+    fn typed_properties() {
+        let proc = Process::new();
+        let job = Job::root();
+        let child = Process::create(&job, "child").unwrap();
+        let (socket, _peer) = Socket::create(SocketMode::Stream);
+        let hj = proc.add_handle(Handle::with_default_rights(job)).unwrap();
+        let hc = proc.add_handle(Handle::with_default_rights(child.clone())).unwrap();
+        let hs = proc.add_handle(Handle::with_default_rights(socket.clone())).unwrap();
+
+        assert_eq!(proc.get_property(hj, PropertyId::JobKillOnOom), Ok(Property::JobKillOnOom(false)));
+        proc.set_property(hj, Property::JobKillOnOom(true)).unwrap();
+        assert_eq!(proc.get_property(hj, PropertyId::JobKillOnOom), Ok(Property::JobKillOnOom(true)));
+
+        assert_eq!(proc.get_property(hc, PropertyId::ProcessExitCode).err(), Some(ZxError::BAD_STATE));
+        child.exit(7);
+        assert_eq!(proc.get_property(hc, PropertyId::ProcessExitCode), Ok(Property::ProcessExitCode(7)));
+        assert_eq!(
+            proc.set_property(hc, Property::ProcessExitCode(0)).err(),
+            Some(ZxError::NOT_SUPPORTED)
+        );
+
+        proc.set_property(hs, Property::SocketRxThreshold(16)).unwrap();
+        assert_eq!(socket.rx_threshold(), 16);
+        assert_eq!(
+            proc.set_property(hs, Property::SocketTxThreshold(usize::MAX)).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        assert_eq!(proc.get_property(hs, PropertyId::SocketTxThreshold), Ok(Property::SocketTxThreshold(0)));
+        assert_eq!(Property::SocketRxThreshold(1).id(), PropertyId::SocketRxThreshold);
+    }
+}
@@ -0,0 +1,198 @@
+use spin::Mutex;
+use alloc::sync::Arc;
+
+use crate::error::*;
+use crate::object::*;
+use crate::impl_kobject;
+use super::Process;
+
+/// 线程对象，线程是进程中的执行单元，每个线程都属于且只属于一个进程。
+/// 这里没有真正的 CPU 上下文，线程只用一个状态机来模拟它的生命周期。
+pub struct Thread {
+    base: KObjectBase,
+    proc: Arc<Process>,         //线程所属的进程，线程存活期间进程不会被销毁
+    inner: Mutex<ThreadInner>,  //线程对象的可变部分
+}
+impl_kobject!(Thread, THREAD
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_THREAD
+    }
+    fn related_koid(&self) -> KoID {
+        self.proc.id()
+    }
+);
+
+struct ThreadInner {
+    state: ThreadState,
+}
+
+/// 线程的生命周期状态
+///
+/// New --start--> Running <--suspend/resume--> Suspended
+///                Running <--block/unblock-->  Blocked
+/// 任意状态 --kill--> Dying --exit--> Dead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    /// 刚创建，还没有启动
+    New,
+    /// 正在运行
+    Running,
+    /// 被挂起
+    Suspended,
+    /// 阻塞在某个等待操作上
+    Blocked,
+    /// 已经被杀死，正在等待执行流退出
+    Dying,
+    /// 已经退出
+    Dead,
+}
+
+impl Thread {
+    /// 在进程 `proc` 中创建一个新线程，并把它加入进程的线程列表，进程已经退出时返回 BAD_STATE
+    pub fn create(proc: &Arc<Process>, name: &str) -> ZxResult<Arc<Self>> {
+        let thread = Arc::new(Thread {
+            base: KObjectBase::default(),
+            proc: proc.clone(),
+            inner: Mutex::new(ThreadInner {
+                state: ThreadState::New,
+            }),
+        });
+        thread.base.set_name(name);
+        proc.add_thread(thread.clone())?;
+        Ok(thread)
+    }
+    /// 获取线程所属的进程
+    pub fn proc(&self) -> &Arc<Process> {
+        &self.proc
+    }
+    /// 获取线程当前的状态
+    pub fn state(&self) -> ThreadState {
+        self.inner.lock().state
+    }
+    /// 启动线程，只有新创建的线程可以启动
+    pub fn start(&self) -> ZxResult<()> {
+        self.transition(ThreadState::New, ThreadState::Running)
+    }
+    /// 挂起一个正在运行的线程
+    pub fn suspend(&self) -> ZxResult<()> {
+        self.transition(ThreadState::Running, ThreadState::Suspended)
+    }
+    /// 恢复一个被挂起的线程
+    pub fn resume(&self) -> ZxResult<()> {
+        self.transition(ThreadState::Suspended, ThreadState::Running)
+    }
+    /// 线程进入阻塞状态，由各种等待操作调用
+    pub fn block(&self) -> ZxResult<()> {
+        self.transition(ThreadState::Running, ThreadState::Blocked)
+    }
+    /// 线程从阻塞中被唤醒
+    pub fn unblock(&self) -> ZxResult<()> {
+        self.transition(ThreadState::Blocked, ThreadState::Running)
+    }
+    /// 线程主动退出，退出后从所属进程的线程列表中移除
+    pub fn exit(&self) {
+        {
+            let mut inner = self.inner.lock();
+            if inner.state == ThreadState::Dead {
+                return;
+            }
+            inner.state = ThreadState::Dead;
+        } //先放掉线程的锁，再去拿进程的锁，避免两把锁嵌套
+        self.base.signal_set(Signal::SIGNALED); //通知等待者：线程已经终止
+        self.proc.remove_thread(self.id());
+    }
+    /// 杀死线程。
+    /// 还没开始执行或者被挂起的线程直接死亡；
+    /// 正在运行或阻塞的线程先进入 Dying，等它的执行流调用 `exit` 后才真正死亡。
+    pub fn kill(&self) {
+        let state = {
+            let mut inner = self.inner.lock();
+            match inner.state {
+                ThreadState::Running | ThreadState::Blocked => {
+                    inner.state = ThreadState::Dying;
+                    return;
+                }
+                state => state,
+            }
+        };
+        if state != ThreadState::Dead && state != ThreadState::Dying {
+            self.exit();
+        }
+    }
+    /// 线程是否已经被杀死（正在死亡或已经死亡）
+    pub fn is_dying(&self) -> bool {
+        matches!(self.state(), ThreadState::Dying | ThreadState::Dead)
+    }
+    ///状态从 `from` 转换到 `to`，当前状态不是 `from` 时返回 BAD_STATE
+    fn transition(&self, from: ThreadState, to: ThreadState) -> ZxResult<()> {
+        let mut inner = self.inner.lock();
+        if inner.state != from {
+            return Err(ZxError::BAD_STATE);
+        }
+        inner.state = to;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod thread_test {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn create() {
+        let proc = Process::new();
+        let thread = Thread::create(&proc, "thread").unwrap();
+        assert_eq!(thread.type_name(), "Thread");
+        assert_eq!(thread.name(), "thread");
+        assert_eq!(thread.state(), ThreadState::New);
+        assert!(Arc::ptr_eq(thread.proc(), &proc));
+        assert_eq!(proc.thread_ids(), vec![thread.id()]);
+    }
+
+    #[test]
+    fn lifecycle() {
+        let proc = Process::new();
+        let thread = Thread::create(&proc, "thread").unwrap();
+        // 没启动的线程不能挂起
+        assert_eq!(thread.suspend().err(), Some(ZxError::BAD_STATE));
+        thread.start().unwrap();
+        assert_eq!(thread.start().err(), Some(ZxError::BAD_STATE));
+        assert_eq!(thread.state(), ThreadState::Running);
+
+        thread.suspend().unwrap();
+        assert_eq!(thread.state(), ThreadState::Suspended);
+        thread.resume().unwrap();
+        thread.block().unwrap();
+        assert_eq!(thread.state(), ThreadState::Blocked);
+        thread.unblock().unwrap();
+        assert_eq!(thread.state(), ThreadState::Running);
+
+        assert!(!thread.signal().contains(Signal::SIGNALED));
+        thread.exit();
+        assert_eq!(thread.state(), ThreadState::Dead);
+        assert!(thread.signal().contains(Signal::SIGNALED));
+        assert!(proc.thread_ids().is_empty());
+    }
+
+    #[test]
+    fn kill() {
+        let proc = Process::new();
+        // 没启动的线程被杀死后直接死亡
+        let t0 = Thread::create(&proc, "t0").unwrap();
+        t0.kill();
+        assert_eq!(t0.state(), ThreadState::Dead);
+
+        // 运行中的线程先进入 Dying，退出后才死亡
+        let t1 = Thread::create(&proc, "t1").unwrap();
+        t1.start().unwrap();
+        t1.kill();
+        assert_eq!(t1.state(), ThreadState::Dying);
+        assert!(t1.is_dying());
+        assert_eq!(t1.block().err(), Some(ZxError::BAD_STATE));
+        assert_eq!(proc.thread_ids(), vec![t1.id()]);
+        t1.exit();
+        assert_eq!(t1.state(), ThreadState::Dead);
+        assert!(proc.thread_ids().is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+//! 内核时间。
+//! 时刻 `Time` 和时长 `Duration` 都以纳秒为单位，运算是饱和的：
+//! 无限期的截止时间加上任何时长都还是无限期，不会溢出回绕。
+//!
+//! 内核通过 `Clock` 获取当前时间。默认的内核时钟是软件维护的 `FakeClock`，
+//! 时间只在测试调用 `advance` 时前进，这样在测试中就能精确地控制超时何时发生；
+//! 内核之外的代码要拨动时钟，就用 `set_kernel_clock` 注入自己持有的 `FakeClock`，
+//! 在宿主操作系统上运行时可以换成基于操作系统时钟的 `HostClock`。
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::sync::atomic::{AtomicI64, Ordering};
+use spin::{Mutex, Once};
+
+mod timer;
+pub use self::timer::*;
+
+/// 单调时钟上的一个时刻，单位是纳秒
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time(i64);
+
+/// 两个时刻之间的时长，单位是纳秒，可以为负
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration(i64);
+
+/// 永远不会到达的截止时间，用于无限期等待
+pub const INFINITE: Time = Time::INFINITE;
+
+/// 早已过去的截止时间，用于不等待、只检查一次
+pub const INFINITE_PAST: Time = Time::INFINITE_PAST;
+
+impl Time {
+    /// 永远不会到达的时刻
+    pub const INFINITE: Self = Time(i64::MAX);
+    /// 早已过去的时刻
+    pub const INFINITE_PAST: Self = Time(i64::MIN);
+    /// 时钟的起点
+    pub const ZERO: Self = Time(0);
+
+    /// 从纳秒数构造时刻
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Time(nanos)
+    }
+    /// 转换成纳秒数
+    pub const fn as_nanos(self) -> i64 {
+        self.0
+    }
+    /// 从现在起经过 `duration` 之后的截止时间
+    pub fn after(duration: Duration) -> Self {
+        clock_get_monotonic() + duration
+    }
+    /// 截止时间是否已经过去，INFINITE 永远不会过去
+    pub fn is_expired(self) -> bool {
+        self != Self::INFINITE && clock_get_monotonic() >= self
+    }
+}
+
+impl Duration {
+    /// 无限长的时长
+    pub const INFINITE: Self = Duration(i64::MAX);
+    /// 零时长
+    pub const ZERO: Self = Duration(0);
+
+    /// 从纳秒数构造时长
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Duration(nanos)
+    }
+    /// 从微秒数构造时长
+    pub const fn from_micros(micros: i64) -> Self {
+        Duration(micros.saturating_mul(1_000))
+    }
+    /// 从毫秒数构造时长
+    pub const fn from_millis(millis: i64) -> Self {
+        Duration(millis.saturating_mul(1_000_000))
+    }
+    /// 从秒数构造时长
+    pub const fn from_secs(secs: i64) -> Self {
+        Duration(secs.saturating_mul(1_000_000_000))
+    }
+    /// 转换成纳秒数
+    pub const fn as_nanos(self) -> i64 {
+        self.0
+    }
+}
+
+impl Add<Duration> for Time {
+    type Output = Time;
+    fn add(self, rhs: Duration) -> Time {
+        Time(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl AddAssign<Duration> for Time {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Time {
+    type Output = Time;
+    fn sub(self, rhs: Duration) -> Time {
+        Time(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl SubAssign<Duration> for Time {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub<Time> for Time {
+    type Output = Duration;
+    fn sub(self, rhs: Time) -> Duration {
+        Duration(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+    fn add(self, rhs: Duration) -> Duration {
+        Duration(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+    fn sub(self, rhs: Duration) -> Duration {
+        Duration(self.0.saturating_sub(rhs.0))
+    }
+}
+
+/// 到期时被调用的回调，参数是触发时的时间
+pub type TimerCallback = Box<dyn FnOnce(Time) + Send>;
+
+/// 单调时钟，负责报告当前时间，并在截止时间到达时调用回调
+pub trait Clock: Send + Sync {
+    /// 当前时间
+    fn now(&self) -> Time;
+    /// 在时间到达 `deadline` 之后调用 `callback`，截止时间已经过去时立即调用
+    fn schedule(&self, deadline: Time, callback: TimerCallback);
+}
+
+static SOFTWARE_CLOCK: Once<Arc<FakeClock>> = Once::new(); //默认的软件时钟
+static KERNEL_CLOCK: Mutex<Option<Arc<dyn Clock>>> = Mutex::new(None); //注入的内核时钟
+
+fn software_clock() -> &'static Arc<FakeClock> {
+    SOFTWARE_CLOCK.call_once(|| Arc::new(FakeClock::new()))
+}
+
+/// 替换内核时钟，之后所有的等待操作都用它判断超时
+pub fn set_kernel_clock(clock: Arc<dyn Clock>) {
+    *KERNEL_CLOCK.lock() = Some(clock);
+}
+
+/// 获取当前的内核时钟
+pub fn kernel_clock() -> Arc<dyn Clock> {
+    match &*KERNEL_CLOCK.lock() {
+        Some(clock) => clock.clone(),
+        None => software_clock().clone(),
+    }
+}
+
+/// 获取当前的单调时间
+pub fn clock_get_monotonic() -> Time {
+    kernel_clock().now()
+}
+
+/// 让默认的软件时钟前进 `duration`，只给测试用
+#[cfg(test)]
+pub(crate) fn advance(duration: Duration) {
+    software_clock().advance(duration);
+}
+
+/// 内核时钟是全局的，测试是并行跑的。
+/// 拨动内核时钟或者依赖有限截止时间的测试都要先拿到这把锁，免得互相提前触发对方的超时。
+#[cfg(test)]
+pub(crate) static CLOCK_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// 手动拨动的时钟，时间只在调用 `advance` 或 `set` 时前进，用于测试
+#[derive(Default)]
+pub struct FakeClock {
+    now: AtomicI64,
+    pending: Mutex<Vec<(Time, TimerCallback)>>, //还没有到期的回调
+}
+
+impl FakeClock {
+    /// 创建一个从 0 开始的时钟
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 让时钟前进 `duration`，并触发所有到期的回调
+    pub fn advance(&self, duration: Duration) {
+        self.set(self.now() + duration);
+    }
+    /// 把时钟拨到 `now`，时钟不会倒退
+    pub fn set(&self, now: Time) {
+        let now = Time(self.now.fetch_max(now.0, Ordering::SeqCst).max(now.0));
+        //先把到期的回调取出来，放掉锁之后再调用，回调中可能会重新设置定时器
+        let expired: Vec<_> = {
+            let mut pending = self.pending.lock();
+            let (expired, rest) = core::mem::take(&mut *pending)
+                .into_iter()
+                .partition(|(deadline, _)| *deadline <= now);
+            *pending = rest;
+            expired
+        };
+        for (_, callback) in expired {
+            callback(now);
+        }
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Time {
+        Time(self.now.load(Ordering::SeqCst))
+    }
+    fn schedule(&self, deadline: Time, callback: TimerCallback) {
+        let now = self.now();
+        if deadline <= now {
+            callback(now);
+        } else {
+            self.pending.lock().push((deadline, callback));
+        }
+    }
+}
+
+/// 基于宿主操作系统单调时钟的时钟，时间从创建时刻开始计算
+#[cfg(feature = "hosted")]
+pub struct HostClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "hosted")]
+impl HostClock {
+    /// 创建一个从当前时刻开始计时的时钟
+    pub fn new() -> Self {
+        HostClock {
+            start: std::time::Instant::now(),
+        }
+    }
+    fn elapsed(start: std::time::Instant) -> Time {
+        Time(start.elapsed().as_nanos().min(i64::MAX as u128) as i64)
+    }
+}
+
+#[cfg(feature = "hosted")]
+impl Default for HostClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "hosted")]
+impl Clock for HostClock {
+    fn now(&self) -> Time {
+        Self::elapsed(self.start)
+    }
+    fn schedule(&self, deadline: Time, callback: TimerCallback) {
+        if deadline == INFINITE {
+            return; //永远不会到期
+        }
+        //每个定时器用一个宿主线程睡到截止时间，对教学内核来说足够了
+        let delay = (deadline - self.now()).as_nanos().max(0) as u64;
+        let start = self.start;
+        std::thread::spawn(move || {
+            std::thread::sleep(core::time::Duration::from_nanos(delay));
+            callback(Self::elapsed(start));
+        });
+    }
+}
+
+#[cfg(test)]
+mod time_test {
+    use super::*;
+
+    #[test]
+    fn saturating() {
+        let t = Time::from_nanos(100);
+        assert_eq!(t + Duration::from_nanos(50), Time::from_nanos(150));
+        assert_eq!(t - Duration::from_nanos(150), Time::from_nanos(-50));
+        assert_eq!(Time::from_nanos(150) - t, Duration::from_nanos(50));
+        assert_eq!(INFINITE + Duration::from_secs(1), INFINITE);
+        assert_eq!(t + Duration::INFINITE, INFINITE);
+        assert_eq!(INFINITE_PAST - Duration::from_secs(1), INFINITE_PAST);
+        assert_eq!(INFINITE - INFINITE_PAST, Duration::INFINITE);
+        assert_eq!(Duration::from_secs(i64::MAX), Duration::INFINITE);
+        assert_eq!(Duration::from_millis(3).as_nanos(), 3_000_000);
+        assert!(INFINITE_PAST < Time::ZERO && Time::ZERO < INFINITE);
+    }
+
+    #[test]
+    fn fake_clock() {
+        let clock = FakeClock::new();
+        assert_eq!(clock.now(), Time::ZERO);
+        clock.advance(Duration::from_micros(2));
+        assert_eq!(clock.now(), Time::from_nanos(2000));
+        // 时钟不会倒退
+        clock.set(Time::from_nanos(1000));
+        assert_eq!(clock.now(), Time::from_nanos(2000));
+    }
+
+    #[test]
+    fn deadline() {
+        let _clock = CLOCK_TEST_LOCK.lock();
+        assert!(INFINITE_PAST.is_expired());
+        assert!(!INFINITE.is_expired());
+        let deadline = Time::after(Duration::from_secs(1));
+        assert!(deadline > clock_get_monotonic());
+    }
+}
@@ -0,0 +1,160 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::impl_kobject,
+    crate::object::*,
+    alloc::sync::{Arc, Weak},
+    spin::Mutex,
+};
+
+/// 定时器对象，到达截止时间时置位 SIGNALED
+pub struct Timer {
+    base: KObjectBase,
+    clock: Arc<dyn Clock>, //驱动定时器的时钟
+    inner: Mutex<TimerInner>,
+}
+
+impl_kobject!(Timer, TIMER
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_TIMER
+    }
+);
+
+#[derive(Default)]
+struct TimerInner {
+    deadline: Option<Time>, //尚未触发的截止时间，没有设置或已经触发时为 None
+    slack: Duration,        //允许的延迟
+    generation: u64,        //每次 set/cancel 都加一，用来识别已经过时的时钟回调
+}
+
+impl Timer {
+    /// 创建一个由内核时钟驱动的定时器
+    pub fn new() -> Arc<Self> {
+        Self::with_clock(kernel_clock())
+    }
+    /// 创建一个由 `clock` 驱动的定时器
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Arc<Self> {
+        Arc::new(Timer {
+            base: KObjectBase::default(),
+            clock,
+            inner: Mutex::new(TimerInner::default()),
+        })
+    }
+    /// 启动定时器，在 `deadline` 之后触发。
+    /// `slack` 是允许的延迟，定时器会在 [deadline, deadline + slack] 之间触发；
+    /// 之前设置的截止时间被替换，SIGNALED 被清除；`slack` 为负时返回 OUT_OF_RANGE。
+    pub fn set(self: &Arc<Self>, deadline: Time, slack: Duration) -> ZxResult<()> {
+        if slack < Duration::ZERO {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
+        let generation = {
+            let mut inner = self.inner.lock();
+            inner.generation += 1;
+            inner.deadline = Some(deadline);
+            inner.slack = slack;
+            self.base.signal_clear(Signal::SIGNALED);
+            inner.generation
+        };
+        //回调只持有弱引用，定时器被销毁后回调什么都不做
+        let timer = Arc::downgrade(self);
+        self.clock.schedule(
+            deadline,
+            Box::new(move |_now| {
+                if let Some(timer) = Weak::upgrade(&timer) {
+                    timer.fire(generation);
+                }
+            }),
+        );
+        Ok(())
+    }
+    /// 取消尚未触发的定时器，并清除 SIGNALED
+    pub fn cancel(&self) {
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+        inner.deadline = None;
+        self.base.signal_clear(Signal::SIGNALED);
+    }
+    /// 尚未触发的截止时间
+    pub fn deadline(&self) -> Option<Time> {
+        self.inner.lock().deadline
+    }
+    /// 当前设置的允许延迟
+    pub fn slack(&self) -> Duration {
+        self.inner.lock().slack
+    }
+    /// 时钟回调：只有回调仍对应最近一次 set 时才触发
+    fn fire(&self, generation: u64) {
+        let mut inner = self.inner.lock();
+        if inner.generation != generation {
+            return;
+        }
+        inner.deadline = None;
+        self.base.signal_set(Signal::SIGNALED);
+    }
+}
+
+#[cfg(test)]
+mod timer_test {
+    use super::*;
+
+    #[test]
+    fn set_fire() {
+        let clock = Arc::new(FakeClock::new());
+        let timer = Timer::with_clock(clock.clone());
+        assert_eq!(timer.type_name(), "Timer");
+        timer.set(Time::from_nanos(1000), Duration::from_nanos(10)).unwrap();
+        assert_eq!(timer.deadline(), Some(Time::from_nanos(1000)));
+        assert_eq!(timer.slack(), Duration::from_nanos(10));
+        assert_eq!(
+            timer.set(INFINITE, Duration::from_nanos(-1)).err(),
+            Some(ZxError::OUT_OF_RANGE)
+        );
+        clock.advance(Duration::from_nanos(999));
+        assert!(!timer.signal().contains(Signal::SIGNALED));
+        clock.advance(Duration::from_nanos(1));
+        assert!(timer.signal().contains(Signal::SIGNALED));
+        assert_eq!(timer.deadline(), None);
+
+        // 重新设置会清除 SIGNALED，截止时间已经过去时立即触发
+        timer.set(Time::from_nanos(2000), Duration::from_nanos(0)).unwrap();
+        assert!(!timer.signal().contains(Signal::SIGNALED));
+        timer.set(Time::from_nanos(500), Duration::from_nanos(0)).unwrap();
+        assert!(timer.signal().contains(Signal::SIGNALED));
+    }
+
+    #[test]
+    fn cancel_and_reset() {
+        let clock = Arc::new(FakeClock::new());
+        let timer = Timer::with_clock(clock.clone());
+        timer.set(Time::from_nanos(100), Duration::from_nanos(0)).unwrap();
+        timer.cancel();
+        assert_eq!(timer.deadline(), None);
+        clock.advance(Duration::from_nanos(100));
+        assert!(!timer.signal().contains(Signal::SIGNALED));
+
+        // 旧的截止时间被新的替换
+        timer.set(Time::from_nanos(300), Duration::from_nanos(0)).unwrap();
+        timer.set(Time::from_nanos(500), Duration::from_nanos(0)).unwrap();
+        clock.set(Time::from_nanos(300));
+        assert!(!timer.signal().contains(Signal::SIGNALED));
+        clock.set(Time::from_nanos(500));
+        assert!(timer.signal().contains(Signal::SIGNALED));
+        timer.cancel();
+        assert!(!timer.signal().contains(Signal::SIGNALED));
+    }
+
+    #[test]
+    fn wait_timer() {
+        let clock = Arc::new(FakeClock::new());
+        let timer = Timer::with_clock(clock.clone());
+        timer.set(Time::from_nanos(100), Duration::from_nanos(0)).unwrap();
+        let object: Arc<dyn KernelObject> = timer.clone();
+        let waiter = std::thread::spawn(move || object_wait_one(&object, Signal::SIGNALED, INFINITE));
+        clock.advance(Duration::from_nanos(100));
+        assert_eq!(waiter.join().unwrap(), Ok(Signal::SIGNALED));
+        // 定时器销毁之后到期的回调什么都不做
+        timer.set(Time::from_nanos(200), Duration::from_nanos(0)).unwrap();
+        drop(timer);
+        clock.advance(Duration::from_nanos(100));
+    }
+}
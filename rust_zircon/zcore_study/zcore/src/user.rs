@@ -0,0 +1,319 @@
+//! 用户指针。
+//! 系统调用的参数往往是用户地址空间中的指针，内核不能直接解引用它们：地址可能没有映射、没有权限、没有对齐。
+//! 这里的 `UserPtr` 把地址和它所在的地址空间绑在一起，读写时逐项检查，出错返回 ZxError 而不是 panic。
+use crate::error::*;
+use crate::task::Process;
+use crate::vm::{round_down_pages, MMUFlags, Vmar, PAGE_SIZE};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use spin::Mutex;
+
+/// 可以按字节读写的地址空间
+pub trait AddressSpace: Sync {
+    /// 从虚拟地址 `vaddr` 读取数据填满 `buf`
+    fn read_memory(&self, vaddr: usize, buf: &mut [u8]) -> ZxResult<()>;
+    /// 把 `buf` 写到虚拟地址 `vaddr`
+    fn write_memory(&self, vaddr: usize, buf: &[u8]) -> ZxResult<()>;
+}
+
+impl AddressSpace for Vmar {
+    fn read_memory(&self, vaddr: usize, buf: &mut [u8]) -> ZxResult<()> {
+        for_each_page(vaddr, buf.len(), |page_vaddr, range| {
+            let entry = self.translate(page_vaddr, MMUFlags::READ)?;
+            let offset = entry.vmo_offset + (page_vaddr - round_down_pages(page_vaddr));
+            entry.vmo.read(offset, &mut buf[range])
+        })
+    }
+    fn write_memory(&self, vaddr: usize, buf: &[u8]) -> ZxResult<()> {
+        for_each_page(vaddr, buf.len(), |page_vaddr, range| {
+            let entry = self.translate(page_vaddr, MMUFlags::WRITE)?;
+            let offset = entry.vmo_offset + (page_vaddr - round_down_pages(page_vaddr));
+            entry.vmo.write(offset, &buf[range])
+        })
+    }
+}
+
+impl AddressSpace for Process {
+    fn read_memory(&self, vaddr: usize, buf: &mut [u8]) -> ZxResult<()> {
+        self.vmar().read_memory(vaddr, buf)
+    }
+    fn write_memory(&self, vaddr: usize, buf: &[u8]) -> ZxResult<()> {
+        self.vmar().write_memory(vaddr, buf)
+    }
+}
+
+/// 把 `[vaddr, vaddr + len)` 按页切开，对每一段调用 `f(这一段的起始地址, 这一段在整个范围中的位置)`
+fn for_each_page(
+    vaddr: usize,
+    len: usize,
+    mut f: impl FnMut(usize, core::ops::Range<usize>) -> ZxResult<()>,
+) -> ZxResult<()> {
+    vaddr.checked_add(len).ok_or(ZxError::INVALID_ARGS)?;
+    let mut pos = 0;
+    while pos < len {
+        let addr = vaddr + pos;
+        let n = (PAGE_SIZE - addr % PAGE_SIZE).min(len - pos);
+        f(addr, pos..pos + n)?;
+        pos += n;
+    }
+    Ok(())
+}
+
+/// 一块普通的内存，假装自己是从 `base` 开始的地址空间，方便在测试中使用用户指针
+pub struct MemoryBuffer {
+    base: usize,
+    data: Mutex<Vec<u8>>,
+}
+
+impl MemoryBuffer {
+    /// 创建一块从 `base` 开始、大小为 `size` 的全 0 内存
+    pub fn new(base: usize, size: usize) -> Self {
+        MemoryBuffer {
+            base,
+            data: Mutex::new(vec![0; size]),
+        }
+    }
+    /// 把地址范围换算成缓冲区中的下标范围，越界返回 NOT_FOUND，和访问没有映射的地址一样
+    fn range(&self, vaddr: usize, len: usize, size: usize) -> ZxResult<core::ops::Range<usize>> {
+        let begin = vaddr.checked_sub(self.base).ok_or(ZxError::NOT_FOUND)?;
+        match begin.checked_add(len) {
+            Some(end) if end <= size => Ok(begin..end),
+            _ => Err(ZxError::NOT_FOUND),
+        }
+    }
+}
+
+impl AddressSpace for MemoryBuffer {
+    fn read_memory(&self, vaddr: usize, buf: &mut [u8]) -> ZxResult<()> {
+        let data = self.data.lock();
+        let range = self.range(vaddr, buf.len(), data.len())?;
+        buf.copy_from_slice(&data[range]);
+        Ok(())
+    }
+    fn write_memory(&self, vaddr: usize, buf: &[u8]) -> ZxResult<()> {
+        let mut data = self.data.lock();
+        let range = self.range(vaddr, buf.len(), data.len())?;
+        data[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// 可以直接从字节构造的“纯数据”类型：任意的字节序列都是它的合法值。
+///
+/// # Safety
+///
+/// 实现者必须保证类型中没有指针、引用、枚举、bool 之类对取值有要求的成员，也没有填充字节。
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($t:ty)*) => { $( unsafe impl Pod for $t {} )* };
+}
+impl_pod!(u8 u16 u32 u64 usize i8 i16 i32 i64 isize);
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// 用户指针的读写策略
+pub trait Policy {}
+/// 只能读
+pub enum In {}
+/// 只能写
+pub enum Out {}
+/// 既能读又能写
+pub enum InOut {}
+impl Policy for In {}
+impl Policy for Out {}
+impl Policy for InOut {}
+
+/// 指向地址空间 `aspace` 中虚拟地址 `addr` 处一个 `T` 的用户指针
+pub struct UserPtr<'a, T, P: Policy> {
+    addr: usize,
+    aspace: &'a dyn AddressSpace,
+    mark: PhantomData<(T, P)>,
+}
+
+/// 只读的用户指针，用于系统调用的输入参数
+pub type UserInPtr<'a, T> = UserPtr<'a, T, In>;
+/// 只写的用户指针，用于系统调用的输出参数
+pub type UserOutPtr<'a, T> = UserPtr<'a, T, Out>;
+/// 可读可写的用户指针
+pub type UserInOutPtr<'a, T> = UserPtr<'a, T, InOut>;
+
+impl<'a, T: Pod, P: Policy> UserPtr<'a, T, P> {
+    /// 用地址空间和虚拟地址构造一个用户指针，这一步不做任何检查
+    pub fn new(aspace: &'a dyn AddressSpace, addr: usize) -> Self {
+        UserPtr {
+            addr,
+            aspace,
+            mark: PhantomData,
+        }
+    }
+    /// 指针指向的虚拟地址
+    pub fn as_addr(&self) -> usize {
+        self.addr
+    }
+    /// 是否为空指针
+    pub fn is_null(&self) -> bool {
+        self.addr == 0
+    }
+    /// 向后偏移 `count` 个元素
+    pub fn add(&self, count: usize) -> Self {
+        Self::new(self.aspace, self.addr.wrapping_add(count.wrapping_mul(size_of::<T>())))
+    }
+    /// 检查从这个指针开始的 `len` 个元素：不能为空、必须对齐、不能越过地址空间的末尾。
+    /// 返回这些元素的总字节数。
+    fn check(&self, len: usize) -> ZxResult<usize> {
+        if self.is_null() || !self.addr.is_multiple_of(align_of::<T>()) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let size = len.checked_mul(size_of::<T>()).ok_or(ZxError::INVALID_ARGS)?;
+        self.addr.checked_add(size).ok_or(ZxError::INVALID_ARGS)?;
+        Ok(size)
+    }
+}
+
+/// 零大小的元素不占用户内存，没法用地址空间限制它的个数，一次最多读这么多个
+const MAX_ZERO_SIZED_LEN: usize = PAGE_SIZE;
+
+/// 访问用户内存时的错误（没有映射、没有权限）对系统调用来说都是参数不合法
+fn user_fault(err: ZxError) -> ZxError {
+    match err {
+        ZxError::NOT_FOUND | ZxError::ACCESS_DENIED | ZxError::OUT_OF_RANGE => ZxError::INVALID_ARGS,
+        err => err,
+    }
+}
+
+/// 可读的策略
+pub trait Read: Policy {}
+/// 可写的策略
+pub trait Write: Policy {}
+impl Read for In {}
+impl Read for InOut {}
+impl Write for Out {}
+impl Write for InOut {}
+
+impl<T: Pod, P: Read> UserPtr<'_, T, P> {
+    /// 读出指针指向的值
+    pub fn read(&self) -> ZxResult<T> {
+        self.read_array(1)?.pop().ok_or(ZxError::INVALID_ARGS)
+    }
+    /// 读出从指针开始的 `len` 个元素
+    pub fn read_array(&self, len: usize) -> ZxResult<Vec<T>> {
+        let size = self.check(len)?;
+        if size_of::<T>() == 0 && len > MAX_ZERO_SIZED_LEN {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        //长度由用户决定，不能照着它一次分配好缓冲区：按页读，读成功了才为它分配内存，
+        //这样分配的内存不会超过真正可读的用户内存，分配失败也只返回 NO_MEMORY
+        let mut bytes = Vec::new();
+        let mut chunk = [0u8; PAGE_SIZE];
+        let mut pos = 0;
+        while pos < size {
+            let n = (size - pos).min(PAGE_SIZE);
+            self.aspace.read_memory(self.addr + pos, &mut chunk[..n]).map_err(user_fault)?;
+            bytes.try_reserve(n).map_err(|_| ZxError::NO_MEMORY)?;
+            bytes.extend_from_slice(&chunk[..n]);
+            pos += n;
+        }
+        let mut values = Vec::new();
+        values.try_reserve_exact(len).map_err(|_| ZxError::NO_MEMORY)?;
+        //按下标而不是按字节块取元素，零大小的类型（例如 `[u8; 0]`）也能得到 `len` 个元素
+        values.extend((0..len).map(|i| {
+            // Safety: T 是 Pod，任意字节都是合法值；偏移不超过 size，read_unaligned 不要求对齐
+            unsafe { core::ptr::read_unaligned(bytes.as_ptr().add(i * size_of::<T>()) as *const T) }
+        }));
+        Ok(values)
+    }
+}
+
+impl<T: Pod, P: Write> UserPtr<'_, T, P> {
+    /// 把 `value` 写到指针指向的位置
+    pub fn write(&self, value: T) -> ZxResult<()> {
+        self.write_array(&[value])
+    }
+    /// 把 `values` 写到从指针开始的位置
+    pub fn write_array(&self, values: &[T]) -> ZxResult<()> {
+        let size = self.check(values.len())?;
+        // Safety: T 是 Pod，没有填充字节，可以安全地当作字节序列来看
+        let bytes = unsafe { core::slice::from_raw_parts(values.as_ptr() as *const u8, size) };
+        self.aspace.write_memory(self.addr, bytes).map_err(user_fault)
+    }
+}
+
+#[cfg(test)]
+mod user_test {
+    use super::*;
+    use crate::vm::Vmo;
+
+    #[test]
+    fn buffer() {
+        let buf = MemoryBuffer::new(0x1000, 16);
+        let ptr: UserInOutPtr<u32> = UserPtr::new(&buf, 0x1004);
+        ptr.write(0xdead_beef).unwrap();
+        assert_eq!(ptr.read(), Ok(0xdead_beef));
+        ptr.add(1).write_array(&[1, 2]).unwrap();
+        assert_eq!(ptr.read_array(3), Ok(vec![0xdead_beef, 1, 2]));
+        let bytes: UserInPtr<u8> = UserPtr::new(&buf, 0x1008);
+        assert_eq!(bytes.read_array(4), Ok(vec![1, 0, 0, 0]));
+
+        // 越界、空指针、没对齐
+        assert_eq!(ptr.read_array(4).err(), Some(ZxError::INVALID_ARGS));
+        let null: UserInPtr<u32> = UserPtr::new(&buf, 0);
+        assert!(null.is_null());
+        assert_eq!(null.read().err(), Some(ZxError::INVALID_ARGS));
+        let unaligned: UserOutPtr<u32> = UserPtr::new(&buf, 0x1001);
+        assert_eq!(unaligned.write(0).err(), Some(ZxError::INVALID_ARGS));
+        let overflow: UserInPtr<u64> = UserPtr::new(&buf, usize::MAX - 7);
+        assert_eq!(overflow.read_array(2).err(), Some(ZxError::INVALID_ARGS));
+    }
+
+    #[test]
+    fn huge_len() {
+        let buf = MemoryBuffer::new(0x1000, 16);
+        // 长度远超过可读的内存时返回错误，而不是先按长度分配内存
+        let bytes: UserInPtr<u8> = UserPtr::new(&buf, 0x1000);
+        assert_eq!(bytes.read_array(usize::MAX / 2).err(), Some(ZxError::INVALID_ARGS));
+        let words: UserInPtr<u32> = UserPtr::new(&buf, 0x1000);
+        assert_eq!(words.read_array(usize::MAX / 8).err(), Some(ZxError::INVALID_ARGS));
+        // 零大小的元素个数有上限
+        let empty: UserInPtr<[u8; 0]> = UserPtr::new(&buf, 0x1000);
+        assert_eq!(empty.read_array(usize::MAX).err(), Some(ZxError::INVALID_ARGS));
+        assert_eq!(empty.read_array(MAX_ZERO_SIZED_LEN).map(|v| v.len()), Ok(MAX_ZERO_SIZED_LEN));
+    }
+
+    #[test]
+    fn zero_sized() {
+        let buf = MemoryBuffer::new(0x1000, 16);
+        let ptr: UserInOutPtr<[u8; 0]> = UserPtr::new(&buf, 0x1004);
+        assert_eq!(ptr.read(), Ok([]));
+        assert_eq!(ptr.read_array(3), Ok(vec![[], [], []]));
+        ptr.write([]).unwrap();
+        let null: UserInPtr<[u8; 0]> = UserPtr::new(&buf, 0);
+        assert_eq!(null.read().err(), Some(ZxError::INVALID_ARGS));
+    }
+
+    #[test]
+    fn process() {
+        let proc = Process::new();
+        let vmo = Vmo::new(2 * PAGE_SIZE).unwrap();
+        let vmar = proc.vmar();
+        let addr = vmar.map(None, vmo.clone(), 0, 2 * PAGE_SIZE, MMUFlags::READ | MMUFlags::WRITE).unwrap();
+        let ro_addr = vmar.map(None, vmo.clone(), 0, PAGE_SIZE, MMUFlags::READ).unwrap();
+
+        // 跨页读写
+        let ptr: UserInOutPtr<[u8; 4]> = UserPtr::new(&*proc, addr + PAGE_SIZE - 2);
+        ptr.write([1, 2, 3, 4]).unwrap();
+        assert_eq!(ptr.read(), Ok([1, 2, 3, 4]));
+        let mut buf = [0u8; 2];
+        vmo.read(PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [3, 4]);
+
+        // 只读映射可以读不能写
+        let ro: UserInOutPtr<u8> = UserPtr::new(&*proc, ro_addr + PAGE_SIZE - 2);
+        assert_eq!(ro.read(), Ok(1));
+        assert_eq!(ro.write(0).err(), Some(ZxError::INVALID_ARGS));
+        // 没有映射的地址
+        let bad: UserInPtr<u8> = UserPtr::new(&*proc, ro_addr + PAGE_SIZE);
+        assert_eq!(bad.read().err(), Some(ZxError::INVALID_ARGS));
+    }
+}
@@ -0,0 +1,36 @@
+//! 虚拟内存相关的内核对象
+use bitflags::bitflags;
+
+pub mod vmo;
+pub use self::vmo::*;
+
+pub mod vmar;
+pub use self::vmar::*;
+
+/// 页大小
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// 地址或大小是否页对齐
+pub fn page_aligned(x: usize) -> bool {
+    x.is_multiple_of(PAGE_SIZE)
+}
+
+/// 向上对齐到页边界，结果超出 usize 范围时返回 None
+pub fn roundup_pages(size: usize) -> Option<usize> {
+    size.checked_add(PAGE_SIZE - 1).map(round_down_pages)
+}
+
+/// 向下对齐到页边界
+pub fn round_down_pages(size: usize) -> usize {
+    size / PAGE_SIZE * PAGE_SIZE
+}
+
+bitflags! {
+    /// 内存访问权限
+    pub struct MMUFlags: u32 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXECUTE = 1 << 2;
+        const RXW = Self::READ.bits | Self::WRITE.bits | Self::EXECUTE.bits;
+    }
+}
@@ -0,0 +1,556 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::object::*,
+    crate::impl_kobject,
+    alloc::collections::BTreeMap,
+    alloc::sync::{Arc, Weak},
+    alloc::vec::Vec,
+    spin::Mutex,
+};
+
+/// 用户地址空间的起始地址
+pub const USER_ASPACE_BASE: usize = 0x0000_0000_0100_0000;
+/// 用户地址空间的大小
+pub const USER_ASPACE_SIZE: usize = 0x0000_7fff_ff00_0000;
+
+/// 虚拟地址区域（Virtual Memory Address Region），代表地址空间中的一段连续范围。
+/// VMAR 构成一棵树：根 VMAR 覆盖整个用户地址空间，每个 VMAR 可以再划分出子区域，
+/// 也可以把 VMO 的一段映射到自己的范围内。
+/// 没有真正的 MMU，整棵树共享一张软件页表，缺页时根据映射把页表项填上。
+pub struct Vmar {
+    base: KObjectBase,
+    addr: usize,                                 //起始地址
+    size: usize,                                 //大小
+    flags: MMUFlags,                             //这个区域内的映射最多能拥有的权限
+    parent: Weak<Vmar>,                          //父区域，根区域没有父区域
+    page_table: Arc<Mutex<PageTable>>,           //整个地址空间共享的页表
+    inner: Mutex<Option<VmarInner>>,             //区域被销毁后为 None
+}
+
+impl_kobject!(Vmar, VMAR
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_VMAR
+    }
+);
+
+#[derive(Default)]
+struct VmarInner {
+    children: Vec<Arc<Vmar>>, //子区域
+    mappings: Vec<VmMapping>, //直接映射在这个区域里的 VMO
+}
+
+/// 一段 VMO 到虚拟地址的映射
+#[derive(Clone)]
+struct VmMapping {
+    addr: usize,
+    size: usize,
+    flags: MMUFlags,
+    vmo: Arc<Vmo>,
+    vmo_offset: usize,
+}
+
+/// 软件页表，key 是虚拟页的起始地址
+type PageTable = BTreeMap<usize, PageTableEntry>;
+
+/// 页表项，记录一个虚拟页对应 VMO 中的哪一页
+#[derive(Clone)]
+pub struct PageTableEntry {
+    /// 页所在的 VMO
+    pub vmo: Arc<Vmo>,
+    /// 页在 VMO 中的偏移，页对齐
+    pub vmo_offset: usize,
+    /// 页的访问权限
+    pub flags: MMUFlags,
+}
+
+impl Vmar {
+    /// 创建一个覆盖整个用户地址空间的根区域，它拥有一张新的页表
+    pub fn new_root() -> Arc<Self> {
+        Arc::new(Vmar {
+            base: KObjectBase::default(),
+            addr: USER_ASPACE_BASE,
+            size: USER_ASPACE_SIZE,
+            flags: MMUFlags::RXW,
+            parent: Weak::new(),
+            page_table: Default::default(),
+            inner: Mutex::new(Some(VmarInner::default())),
+        })
+    }
+    /// 区域的起始地址
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+    /// 区域的大小
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// 区域是否已经被销毁
+    pub fn is_dead(&self) -> bool {
+        self.inner.lock().is_none()
+    }
+    /// 划分一个子区域。
+    /// `offset` 是相对于本区域起始地址的偏移，为 None 时自动找一个足够大的空位。
+    /// 子区域的权限不能超过本区域。
+    pub fn allocate(
+        self: &Arc<Self>,
+        offset: Option<usize>,
+        size: usize,
+        flags: MMUFlags,
+    ) -> ZxResult<Arc<Self>> {
+        if !self.flags.contains(flags) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let mut guard = self.inner.lock();
+        let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+        let addr = self.find_free_area(inner, offset, size)?;
+        let child = Arc::new(Vmar {
+            base: KObjectBase::default(),
+            addr,
+            size,
+            flags,
+            parent: Arc::downgrade(self),
+            page_table: self.page_table.clone(),
+            inner: Mutex::new(Some(VmarInner::default())),
+        });
+        inner.children.push(child.clone());
+        Ok(child)
+    }
+    /// 把 `vmo` 从 `vmo_offset` 开始、长 `len` 的一段以 `flags` 权限映射到本区域中，返回映射的起始地址。
+    /// `vmar_offset` 的含义和 `allocate` 的 `offset` 一样。
+    pub fn map(
+        &self,
+        vmar_offset: Option<usize>,
+        vmo: Arc<Vmo>,
+        vmo_offset: usize,
+        len: usize,
+        flags: MMUFlags,
+    ) -> ZxResult<usize> {
+        if !page_aligned(vmo_offset) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        if !self.flags.contains(flags) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let len = roundup_pages(len).ok_or(ZxError::OUT_OF_RANGE)?;
+        match vmo_offset.checked_add(len) {
+            Some(end) if end <= vmo.size() => {}
+            _ => return Err(ZxError::OUT_OF_RANGE),
+        }
+        let mut guard = self.inner.lock();
+        let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+        let addr = self.find_free_area(inner, vmar_offset, len)?;
+        inner.mappings.push(VmMapping {
+            addr,
+            size: len,
+            flags,
+            vmo,
+            vmo_offset,
+        });
+        Ok(addr)
+    }
+    /// 解除 `[addr, addr + len)` 范围内的映射，范围可以只覆盖映射的一部分。
+    /// 范围和子区域重叠时返回 INVALID_ARGS，子区域中的映射要通过子区域解除。
+    pub fn unmap(&self, addr: usize, len: usize) -> ZxResult<()> {
+        let end = self.check_range(addr, len)?;
+        let mut guard = self.inner.lock();
+        let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+        if inner.overlaps_child(addr, end) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        inner.mappings = core::mem::take(&mut inner.mappings)
+            .into_iter()
+            .flat_map(|m| m.split(addr, end))
+            .filter(|m| m.end() <= addr || m.addr >= end)
+            .collect();
+        self.clear_page_table(addr, end);
+        Ok(())
+    }
+    /// 修改 `[addr, addr + len)` 范围内映射的权限，范围必须完全被映射覆盖。
+    /// 和 `unmap` 一样，范围和子区域重叠时返回 INVALID_ARGS。
+    pub fn protect(&self, addr: usize, len: usize, flags: MMUFlags) -> ZxResult<()> {
+        let end = self.check_range(addr, len)?;
+        if !self.flags.contains(flags) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let mut guard = self.inner.lock();
+        let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+        if inner.overlaps_child(addr, end) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        //先检查范围是否被映射完整覆盖，中间有空洞就返回 NOT_FOUND
+        let mut covered = addr;
+        let mut sorted: Vec<&VmMapping> = inner.mappings.iter().collect();
+        sorted.sort_by_key(|m| m.addr);
+        for m in sorted {
+            if m.addr <= covered && m.end() > covered {
+                covered = m.end();
+            }
+        }
+        if covered < end {
+            return Err(ZxError::NOT_FOUND);
+        }
+        inner.mappings = core::mem::take(&mut inner.mappings)
+            .into_iter()
+            .flat_map(|m| m.split(addr, end))
+            .map(|mut m| {
+                if m.addr >= addr && m.end() <= end {
+                    m.flags = flags;
+                }
+                m
+            })
+            .collect();
+        self.clear_page_table(addr, end); //旧的页表项权限已经过时，下次访问时重新缺页
+        Ok(())
+    }
+    /// 销毁这个区域：解除所有映射，销毁所有子区域，并从父区域中移除
+    pub fn destroy(&self) {
+        let inner = match self.inner.lock().take() {
+            Some(inner) => inner,
+            None => return,
+        };
+        for child in inner.children {
+            child.destroy();
+        }
+        self.clear_page_table(self.addr, self.addr + self.size);
+        if let Some(parent) = self.parent.upgrade() {
+            if let Some(parent_inner) = parent.inner.lock().as_mut() {
+                parent_inner.children.retain(|c| c.id() != self.id());
+            }
+        }
+    }
+    /// 查询页表：虚拟地址 `vaddr` 所在的页当前是否有页表项，有就返回它的权限。
+    /// 只看页表，不会触发缺页。
+    pub fn query(&self, vaddr: usize) -> Option<MMUFlags> {
+        let page = round_down_pages(vaddr);
+        self.page_table.lock().get(&page).map(|e| e.flags)
+    }
+    /// 把虚拟地址 `vaddr` 翻译成它所在的页，`access` 是这次访问需要的权限。
+    /// 页表中没有对应的项时按缺页处理；地址没有被映射返回 NOT_FOUND，权限不够返回 ACCESS_DENIED。
+    pub fn translate(&self, vaddr: usize, access: MMUFlags) -> ZxResult<PageTableEntry> {
+        let page = round_down_pages(vaddr);
+        if let Some(entry) = self.page_table.lock().get(&page) {
+            if !entry.flags.contains(access) {
+                return Err(ZxError::ACCESS_DENIED);
+            }
+            return Ok(entry.clone());
+        }
+        self.handle_page_fault(vaddr, access)
+    }
+    /// 处理缺页：在区域树中找到覆盖 `vaddr` 的映射，检查权限后填上页表项
+    pub fn handle_page_fault(&self, vaddr: usize, access: MMUFlags) -> ZxResult<PageTableEntry> {
+        let page = round_down_pages(vaddr);
+        let guard = self.inner.lock();
+        let inner = guard.as_ref().ok_or(ZxError::NOT_FOUND)?;
+        let mapping = match inner.mappings.iter().find(|m| m.contains(page)) {
+            Some(mapping) => mapping,
+            None => {
+                let child = inner
+                    .children
+                    .iter()
+                    .find(|c| c.addr <= page && page < c.addr + c.size)
+                    .ok_or(ZxError::NOT_FOUND)?
+                    .clone();
+                drop(guard); //先放掉自己的锁再去子区域处理
+                return child.handle_page_fault(vaddr, access);
+            }
+        };
+        if !mapping.flags.contains(access) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let vmo_offset = mapping.vmo_offset + (page - mapping.addr);
+        if vmo_offset >= mapping.vmo.size() {
+            return Err(ZxError::OUT_OF_RANGE); //VMO 在映射之后被缩小了
+        }
+        let entry = PageTableEntry {
+            vmo: mapping.vmo.clone(),
+            vmo_offset,
+            flags: mapping.flags,
+        };
+        //还拿着映射所在区域的锁时填页表项：unmap 和 protect 也是拿着这把锁清除页表项的，
+        //这样它们要么在这之前完成、映射已经找不到，要么在这之后把刚填的页表项一起清掉
+        self.page_table.lock().insert(page, entry.clone());
+        Ok(entry)
+    }
+    /// 在本区域中找一块 `size` 大小的空闲范围，返回它的起始地址。
+    /// 指定了 `offset` 就只检查那里是否空闲，空间不够或者被占用返回 NO_RESOURCES。
+    fn find_free_area(&self, inner: &VmarInner, offset: Option<usize>, size: usize) -> ZxResult<usize> {
+        if size == 0 || !page_aligned(size) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let mut used: Vec<(usize, usize)> = inner
+            .children
+            .iter()
+            .map(|c| (c.addr, c.addr + c.size))
+            .chain(inner.mappings.iter().map(|m| (m.addr, m.end())))
+            .collect();
+        used.sort();
+        let is_free = |begin: usize, end: usize| used.iter().all(|&(b, e)| end <= b || e <= begin);
+        if let Some(offset) = offset {
+            if !page_aligned(offset) {
+                return Err(ZxError::INVALID_ARGS);
+            }
+            match offset.checked_add(size) {
+                Some(end) if end <= self.size => {}
+                _ => return Err(ZxError::INVALID_ARGS),
+            }
+            let addr = self.addr + offset;
+            if !is_free(addr, addr + size) {
+                return Err(ZxError::NO_RESOURCES);
+            }
+            return Ok(addr);
+        }
+        //从低地址开始，依次尝试每个已占用范围之后的位置（首次适配）
+        let candidates = core::iter::once(self.addr).chain(used.iter().map(|&(_, e)| e));
+        for addr in candidates {
+            let fits = addr.checked_add(size).is_some_and(|end| end <= self.addr + self.size);
+            if fits && is_free(addr, addr + size) {
+                return Ok(addr);
+            }
+        }
+        Err(ZxError::NO_RESOURCES)
+    }
+    /// 检查 `[addr, addr + len)` 是否是本区域内页对齐的范围，返回结束地址
+    fn check_range(&self, addr: usize, len: usize) -> ZxResult<usize> {
+        if !page_aligned(addr) || len == 0 {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let end = roundup_pages(len)
+            .and_then(|len| addr.checked_add(len))
+            .ok_or(ZxError::INVALID_ARGS)?;
+        if addr < self.addr || end > self.addr + self.size {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        Ok(end)
+    }
+    /// 清除 `[begin, end)` 范围内的页表项
+    fn clear_page_table(&self, begin: usize, end: usize) {
+        let mut page_table = self.page_table.lock();
+        let pages: Vec<usize> = page_table.range(begin..end).map(|(&p, _)| p).collect();
+        for page in pages {
+            page_table.remove(&page);
+        }
+    }
+}
+
+impl VmarInner {
+    /// `[begin, end)` 是否和某个子区域重叠。
+    /// 页表是整棵树共享的，清除这个范围的页表项会连子区域的一起清掉，所以不允许这样的范围
+    fn overlaps_child(&self, begin: usize, end: usize) -> bool {
+        self.children.iter().any(|c| begin < c.addr + c.size && c.addr < end)
+    }
+}
+
+impl VmMapping {
+    fn end(&self) -> usize {
+        self.addr + self.size
+    }
+    fn contains(&self, addr: usize) -> bool {
+        self.addr <= addr && addr < self.end()
+    }
+    /// 在 `begin` 和 `end` 两个地址处把映射切开，返回切出来的若干段
+    fn split(self, begin: usize, end: usize) -> Vec<VmMapping> {
+        let mut cuts: Vec<usize> = [begin, end]
+            .into_iter()
+            .filter(|&p| self.addr < p && p < self.end())
+            .collect();
+        cuts.push(self.end());
+        let mut pieces = Vec::new();
+        let mut start = self.addr;
+        for cut in cuts {
+            pieces.push(VmMapping {
+                addr: start,
+                size: cut - start,
+                flags: self.flags,
+                vmo: self.vmo.clone(),
+                vmo_offset: self.vmo_offset + (start - self.addr),
+            });
+            start = cut;
+        }
+        pieces
+    }
+}
+
+#[cfg(test)]
+mod vmar_test {
+    use super::*;
+
+    #[test]
+    fn allocate() {
+        let root = Vmar::new_root();
+        assert_eq!(root.type_name(), "Vmar");
+        let child0 = root.allocate(None, 2 * PAGE_SIZE, MMUFlags::READ).unwrap();
+        assert_eq!(child0.addr(), USER_ASPACE_BASE);
+        let child1 = root.allocate(None, PAGE_SIZE, MMUFlags::RXW).unwrap();
+        assert_eq!(child1.addr(), USER_ASPACE_BASE + 2 * PAGE_SIZE);
+
+        // 指定位置
+        let child2 = root.allocate(Some(8 * PAGE_SIZE), PAGE_SIZE, MMUFlags::READ).unwrap();
+        assert_eq!(child2.addr(), USER_ASPACE_BASE + 8 * PAGE_SIZE);
+        assert_eq!(
+            root.allocate(Some(PAGE_SIZE), PAGE_SIZE, MMUFlags::READ).err(),
+            Some(ZxError::NO_RESOURCES)
+        );
+        assert_eq!(
+            root.allocate(Some(1), PAGE_SIZE, MMUFlags::READ).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        // 子区域的权限不能超过父区域
+        assert_eq!(
+            child0.allocate(None, PAGE_SIZE, MMUFlags::WRITE).err(),
+            Some(ZxError::ACCESS_DENIED)
+        );
+        // 空间不够
+        assert_eq!(
+            child0.allocate(None, 3 * PAGE_SIZE, MMUFlags::READ).err(),
+            Some(ZxError::NO_RESOURCES)
+        );
+
+        // 销毁后从父区域中移除，空间可以被重新使用
+        child0.destroy();
+        assert!(child0.is_dead());
+        assert_eq!(child0.allocate(None, PAGE_SIZE, MMUFlags::READ).err(), Some(ZxError::BAD_STATE));
+        let child3 = root.allocate(None, PAGE_SIZE, MMUFlags::READ).unwrap();
+        assert_eq!(child3.addr(), USER_ASPACE_BASE);
+    }
+
+    #[test]
+    fn map_and_fault() {
+        let root = Vmar::new_root();
+        let vmo = Vmo::new(2 * PAGE_SIZE).unwrap();
+        vmo.write(PAGE_SIZE, &[42]).unwrap();
+        let addr = root
+            .map(None, vmo.clone(), 0, 2 * PAGE_SIZE, MMUFlags::READ | MMUFlags::WRITE)
+            .unwrap();
+
+        // 第一次访问前页表是空的，访问时缺页并填上页表项
+        assert_eq!(root.query(addr + PAGE_SIZE), None);
+        let entry = root.translate(addr + PAGE_SIZE + 3, MMUFlags::READ).unwrap();
+        assert!(Arc::ptr_eq(&entry.vmo, &vmo));
+        assert_eq!(entry.vmo_offset, PAGE_SIZE);
+        assert_eq!(root.query(addr + PAGE_SIZE), Some(MMUFlags::READ | MMUFlags::WRITE));
+        let mut buf = [0u8; 1];
+        entry.vmo.read(entry.vmo_offset, &mut buf).unwrap();
+        assert_eq!(buf, [42]);
+
+        // 权限不够
+        assert_eq!(
+            root.translate(addr, MMUFlags::EXECUTE).err(),
+            Some(ZxError::ACCESS_DENIED)
+        );
+        // 没有映射的地址
+        assert_eq!(
+            root.translate(addr + 2 * PAGE_SIZE, MMUFlags::READ).err(),
+            Some(ZxError::NOT_FOUND)
+        );
+        // VMO 范围越界
+        assert_eq!(
+            root.map(None, vmo.clone(), PAGE_SIZE, 2 * PAGE_SIZE, MMUFlags::READ).err(),
+            Some(ZxError::OUT_OF_RANGE)
+        );
+    }
+
+    #[test]
+    fn map_in_child() {
+        let root = Vmar::new_root();
+        let child = root.allocate(None, 4 * PAGE_SIZE, MMUFlags::READ).unwrap();
+        let vmo = Vmo::new(PAGE_SIZE).unwrap();
+        assert_eq!(
+            child.map(None, vmo.clone(), 0, PAGE_SIZE, MMUFlags::WRITE).err(),
+            Some(ZxError::ACCESS_DENIED)
+        );
+        let addr = child.map(Some(PAGE_SIZE), vmo, 0, PAGE_SIZE, MMUFlags::READ).unwrap();
+        assert_eq!(addr, child.addr() + PAGE_SIZE);
+        // 从根区域也能找到子区域中的映射
+        root.translate(addr, MMUFlags::READ).unwrap();
+        // 子区域被销毁后映射也没了
+        child.destroy();
+        assert_eq!(root.query(addr), None);
+        assert_eq!(root.translate(addr, MMUFlags::READ).err(), Some(ZxError::NOT_FOUND));
+    }
+
+    #[test]
+    fn unmap() {
+        let root = Vmar::new_root();
+        let vmo = Vmo::new(3 * PAGE_SIZE).unwrap();
+        let addr = root.map(None, vmo, 0, 3 * PAGE_SIZE, MMUFlags::READ).unwrap();
+        root.translate(addr + PAGE_SIZE, MMUFlags::READ).unwrap();
+
+        // 解除中间一页的映射，两边的映射还在
+        root.unmap(addr + PAGE_SIZE, PAGE_SIZE).unwrap();
+        assert_eq!(root.query(addr + PAGE_SIZE), None);
+        assert_eq!(
+            root.translate(addr + PAGE_SIZE, MMUFlags::READ).err(),
+            Some(ZxError::NOT_FOUND)
+        );
+        assert_eq!(root.translate(addr + 2 * PAGE_SIZE, MMUFlags::READ).unwrap().vmo_offset, 2 * PAGE_SIZE);
+        root.translate(addr, MMUFlags::READ).unwrap();
+        assert_eq!(root.unmap(addr + 1, PAGE_SIZE).err(), Some(ZxError::INVALID_ARGS));
+    }
+
+    #[test]
+    fn protect() {
+        let root = Vmar::new_root();
+        let vmo = Vmo::new(2 * PAGE_SIZE).unwrap();
+        let addr = root.map(None, vmo, 0, 2 * PAGE_SIZE, MMUFlags::READ | MMUFlags::WRITE).unwrap();
+        root.translate(addr + PAGE_SIZE, MMUFlags::WRITE).unwrap();
+
+        root.protect(addr + PAGE_SIZE, PAGE_SIZE, MMUFlags::READ).unwrap();
+        assert_eq!(
+            root.translate(addr + PAGE_SIZE, MMUFlags::WRITE).err(),
+            Some(ZxError::ACCESS_DENIED)
+        );
+        root.translate(addr + PAGE_SIZE, MMUFlags::READ).unwrap();
+        root.translate(addr, MMUFlags::WRITE).unwrap();
+
+        // 范围中有没被映射的部分
+        assert_eq!(
+            root.protect(addr, 3 * PAGE_SIZE, MMUFlags::READ).err(),
+            Some(ZxError::NOT_FOUND)
+        );
+    }
+
+    #[test]
+    fn range_over_child() {
+        let root = Vmar::new_root();
+        let vmo = Vmo::new(PAGE_SIZE).unwrap();
+        let addr0 = root.map(None, vmo.clone(), 0, PAGE_SIZE, MMUFlags::READ).unwrap();
+        let child = root.allocate(None, PAGE_SIZE, MMUFlags::READ).unwrap();
+        let addr1 = child.map(None, vmo, 0, PAGE_SIZE, MMUFlags::READ).unwrap();
+        root.translate(addr1, MMUFlags::READ).unwrap();
+
+        // 范围跨过了子区域，什么也不做
+        assert_eq!(root.unmap(addr0, 2 * PAGE_SIZE).err(), Some(ZxError::INVALID_ARGS));
+        assert_eq!(
+            root.protect(addr0, 2 * PAGE_SIZE, MMUFlags::empty()).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        assert_eq!(root.query(addr1), Some(MMUFlags::READ));
+        root.translate(addr0, MMUFlags::READ).unwrap();
+
+        // 通过子区域解除
+        child.unmap(addr1, PAGE_SIZE).unwrap();
+        assert_eq!(root.translate(addr1, MMUFlags::READ).err(), Some(ZxError::NOT_FOUND));
+        root.unmap(addr0, PAGE_SIZE).unwrap();
+    }
+
+    #[test]
+    fn huge_len() {
+        let root = Vmar::new_root();
+        let vmo = Vmo::new(PAGE_SIZE).unwrap();
+        let addr = root.map(None, vmo.clone(), 0, PAGE_SIZE, MMUFlags::READ).unwrap();
+        // 长度向上对齐到页时会溢出，返回错误而不是 panic
+        for len in [usize::MAX, usize::MAX - 10] {
+            assert_eq!(
+                root.map(None, vmo.clone(), 0, len, MMUFlags::READ).err(),
+                Some(ZxError::OUT_OF_RANGE)
+            );
+            assert_eq!(root.unmap(addr, len).err(), Some(ZxError::INVALID_ARGS));
+            assert_eq!(
+                root.protect(addr, len, MMUFlags::READ).err(),
+                Some(ZxError::INVALID_ARGS)
+            );
+        }
+        root.translate(addr, MMUFlags::READ).unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+use {
+    super::*,
+    crate::error::*,
+    crate::object::*,
+    crate::impl_kobject,
+    alloc::collections::BTreeMap,
+    alloc::sync::Arc,
+    spin::Mutex,
+};
+
+/// 一个物理页帧，这里没有真正的物理内存，用堆上的一块内存来代替。
+/// 页帧用 Arc 包起来，这样快照子对象就能和父对象共享页帧，谁先写谁就复制一份（写时复制）。
+type PageFrame = Arc<[u8; PAGE_SIZE]>;
+
+/// 虚拟内存对象（Virtual Memory Object），代表一段按页管理的内存。
+/// 页是按需提交的：只有写入或者显式 commit 的页才会真正分配，没有分配的页读出来都是 0。
+pub struct Vmo {
+    base: KObjectBase,
+    slice: Option<VmoSlice>, //如果是切片子对象，所有操作都转发给父对象
+    inner: Mutex<VmoInner>,
+}
+
+impl_kobject!(Vmo, VMO
+    fn default_rights(&self) -> Rights {
+        Rights::DEFAULT_VMO
+    }
+);
+
+struct VmoInner {
+    size: usize,                         //VMO 的大小，总是页对齐的
+    pages: BTreeMap<usize, PageFrame>,   //已提交的页，key 是页号
+}
+
+/// 切片子对象指向的父对象范围
+struct VmoSlice {
+    parent: Arc<Vmo>,
+    offset: usize,
+}
+
+/// `op_range` 支持的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmoOpType {
+    /// 提交范围内的页，分配物理页帧
+    Commit,
+    /// 释放范围内的页
+    Decommit,
+    /// 把范围内的数据清零
+    Zero,
+}
+
+/// `create_child` 创建的子对象类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmoChildType {
+    /// 快照：子对象看到的是创建那一刻父对象的内容，之后双方的写入互不可见
+    Snapshot,
+    /// 至少在写时快照：允许子对象看到父对象之后的写入，直到子对象自己写了那一页。
+    /// 这里的实现和 Snapshot 一样在创建时就共享全部页帧，这也满足它的语义。
+    SnapshotAtLeastOnWrite,
+    /// 切片：直接共享父对象的一段范围，双方的写入互相可见
+    Slice,
+}
+
+impl Vmo {
+    /// 创建一个大小为 `size` 的 VMO，大小会向上对齐到页
+    pub fn new(size: usize) -> ZxResult<Arc<Self>> {
+        Ok(Self::new_with_pages(Self::checked_roundup(size)?, BTreeMap::new(), None))
+    }
+    fn new_with_pages(
+        size: usize,
+        pages: BTreeMap<usize, PageFrame>,
+        slice: Option<VmoSlice>,
+    ) -> Arc<Self> {
+        Arc::new(Vmo {
+            base: KObjectBase::default(),
+            slice,
+            inner: Mutex::new(VmoInner { size, pages }),
+        })
+    }
+    /// 创建子对象，`offset` 必须页对齐。
+    /// 快照子对象的范围可以超出父对象，超出的部分读出来是 0；切片子对象必须完全落在父对象之内。
+    pub fn create_child(
+        self: &Arc<Self>,
+        child_type: VmoChildType,
+        offset: usize,
+        size: usize,
+    ) -> ZxResult<Arc<Self>> {
+        if !page_aligned(offset) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let size = Self::checked_roundup(size)?;
+        offset.checked_add(size).ok_or(ZxError::OUT_OF_RANGE)?;
+        match child_type {
+            VmoChildType::Snapshot | VmoChildType::SnapshotAtLeastOnWrite => {
+                let pages = self.share_pages(offset, size);
+                Ok(Self::new_with_pages(size, pages, None))
+            }
+            VmoChildType::Slice => {
+                self.inner.lock().check_range(offset, size)?;
+                //切片的切片直接指向最终拥有页的对象
+                let (parent, offset) = match &self.slice {
+                    Some(slice) => (slice.parent.clone(), slice.offset + offset),
+                    None => (self.clone(), offset),
+                };
+                let slice = VmoSlice { parent, offset };
+                Ok(Self::new_with_pages(size, BTreeMap::new(), Some(slice)))
+            }
+        }
+    }
+    /// 获取 `[offset, offset + size)` 范围内已提交页帧的共享引用，页号从 0 开始重新编号
+    fn share_pages(&self, offset: usize, size: usize) -> BTreeMap<usize, PageFrame> {
+        if let Some(slice) = &self.slice {
+            let size = size.min(self.size().saturating_sub(offset));
+            return slice.parent.share_pages(slice.offset + offset, size);
+        }
+        let inner = self.inner.lock();
+        let first = offset / PAGE_SIZE;
+        inner
+            .pages
+            .range(first..first + size / PAGE_SIZE)
+            .map(|(&idx, frame)| (idx - first, frame.clone()))
+            .collect()
+    }
+    /// 获取 VMO 的大小
+    pub fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+    /// 修改 VMO 的大小，缩小时超出新大小的页会被释放。切片的大小不能修改。
+    pub fn set_size(&self, size: usize) -> ZxResult<()> {
+        if self.slice.is_some() {
+            return Err(ZxError::NOT_SUPPORTED);
+        }
+        let size = Self::checked_roundup(size)?;
+        let mut inner = self.inner.lock();
+        inner.size = size;
+        inner.pages.retain(|&idx, _| idx < size / PAGE_SIZE);
+        Ok(())
+    }
+    /// 已提交的内存字节数，包括和其他对象共享的页。切片自己不拥有任何页，所以总是 0。
+    pub fn committed_bytes(&self) -> usize {
+        self.inner.lock().pages.len() * PAGE_SIZE
+    }
+    /// 只属于这个对象、没有和其他对象共享的已提交字节数
+    pub fn private_bytes(&self) -> usize {
+        let inner = self.inner.lock();
+        let private = inner.pages.values().filter(|f| Arc::strong_count(f) == 1);
+        private.count() * PAGE_SIZE
+    }
+    /// 从 `offset` 处读取数据填满 `buf`，没有提交的页读出来是 0
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> ZxResult<()> {
+        let inner = self.inner.lock();
+        inner.check_range(offset, buf.len())?;
+        if let Some(slice) = &self.slice {
+            drop(inner);
+            return slice.parent.read(slice.offset + offset, buf);
+        }
+        for_each_page(offset, buf.len(), |idx, page_offset, buf_range| {
+            let dst = &mut buf[buf_range];
+            match inner.pages.get(&idx) {
+                Some(frame) => dst.copy_from_slice(&frame[page_offset..page_offset + dst.len()]),
+                None => dst.fill(0),
+            }
+        });
+        Ok(())
+    }
+    /// 把 `buf` 写到 `offset` 处，写到的页会被提交，和别人共享的页会先复制一份
+    pub fn write(&self, offset: usize, buf: &[u8]) -> ZxResult<()> {
+        let mut inner = self.inner.lock();
+        inner.check_range(offset, buf.len())?;
+        if let Some(slice) = &self.slice {
+            drop(inner);
+            return slice.parent.write(slice.offset + offset, buf);
+        }
+        for_each_page(offset, buf.len(), |idx, page_offset, buf_range| {
+            let src = &buf[buf_range];
+            let frame = inner.commit_page(idx);
+            frame[page_offset..page_offset + src.len()].copy_from_slice(src);
+        });
+        Ok(())
+    }
+    /// 对 `[offset, offset + len)` 范围执行操作。
+    /// commit 和 decommit 以页为单位，范围会扩展到页边界；zero 精确到字节。
+    pub fn op_range(&self, op: VmoOpType, offset: usize, len: usize) -> ZxResult<()> {
+        let mut inner = self.inner.lock();
+        inner.check_range(offset, len)?;
+        if let Some(slice) = &self.slice {
+            drop(inner);
+            return slice.parent.op_range(op, slice.offset + offset, len);
+        }
+        match op {
+            VmoOpType::Commit => {
+                for_each_page(offset, len, |idx, _, _| {
+                    inner.commit_page(idx);
+                });
+            }
+            VmoOpType::Decommit => {
+                for_each_page(offset, len, |idx, _, _| {
+                    inner.pages.remove(&idx);
+                });
+            }
+            VmoOpType::Zero => {
+                for_each_page(offset, len, |idx, page_offset, buf_range| {
+                    if buf_range.len() == PAGE_SIZE {
+                        inner.pages.remove(&idx); //整页清零，直接释放就好
+                    } else if let Some(frame) = inner.pages.get_mut(&idx) {
+                        let frame = Arc::make_mut(frame);
+                        frame[page_offset..page_offset + buf_range.len()].fill(0);
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+    fn checked_roundup(size: usize) -> ZxResult<usize> {
+        roundup_pages(size).ok_or(ZxError::OUT_OF_RANGE)
+    }
+}
+
+impl VmoInner {
+    /// 检查范围是否在 VMO 之内
+    fn check_range(&self, offset: usize, len: usize) -> ZxResult<()> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.size => Ok(()),
+            _ => Err(ZxError::OUT_OF_RANGE),
+        }
+    }
+    /// 获取第 `idx` 页的可写引用，还没有提交就分配一个全 0 的页帧，
+    /// 页帧正在和别的对象共享时复制一份私有的（写时复制）
+    fn commit_page(&mut self, idx: usize) -> &mut [u8; PAGE_SIZE] {
+        let frame = self
+            .pages
+            .entry(idx)
+            .or_insert_with(|| Arc::new([0u8; PAGE_SIZE]));
+        Arc::make_mut(frame)
+    }
+}
+
+/// 把 `[offset, offset + len)` 按页切开，对每一段调用 `f(页号, 页内偏移, 这一段在整个范围中的位置)`
+fn for_each_page(
+    offset: usize,
+    len: usize,
+    mut f: impl FnMut(usize, usize, core::ops::Range<usize>),
+) {
+    let mut pos = 0;
+    while pos < len {
+        let addr = offset + pos;
+        let page_offset = addr % PAGE_SIZE;
+        let n = (PAGE_SIZE - page_offset).min(len - pos);
+        f(addr / PAGE_SIZE, page_offset, pos..pos + n);
+        pos += n;
+    }
+}
+
+#[cfg(test)]
+mod vmo_test {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn create() {
+        let vmo = Vmo::new(10).unwrap();
+        assert_eq!(vmo.type_name(), "Vmo");
+        assert_eq!(vmo.size(), PAGE_SIZE);
+        assert_eq!(vmo.committed_bytes(), 0);
+        assert_eq!(Vmo::new(usize::MAX).err(), Some(ZxError::OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn read_write() {
+        let vmo = Vmo::new(2 * PAGE_SIZE).unwrap();
+        // 跨页写入
+        let data = [1u8, 2, 3, 4];
+        vmo.write(PAGE_SIZE - 2, &data).unwrap();
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+
+        let mut buf = [0xffu8; 6];
+        vmo.read(PAGE_SIZE - 3, &mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2, 3, 4, 0]);
+
+        // 越界
+        assert_eq!(vmo.write(2 * PAGE_SIZE - 1, &data).err(), Some(ZxError::OUT_OF_RANGE));
+        assert_eq!(vmo.read(usize::MAX, &mut buf).err(), Some(ZxError::OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn uncommitted_read_is_zero() {
+        let vmo = Vmo::new(PAGE_SIZE).unwrap();
+        let mut buf = vec![0xffu8; PAGE_SIZE];
+        vmo.read(0, &mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == 0));
+        // 读不会提交页
+        assert_eq!(vmo.committed_bytes(), 0);
+    }
+
+    #[test]
+    fn set_size() {
+        let vmo = Vmo::new(3 * PAGE_SIZE).unwrap();
+        vmo.write(2 * PAGE_SIZE, &[1]).unwrap();
+        vmo.set_size(PAGE_SIZE + 1).unwrap();
+        assert_eq!(vmo.size(), 2 * PAGE_SIZE);
+        assert_eq!(vmo.committed_bytes(), 0);
+
+        // 重新扩大后，之前的数据已经没了
+        vmo.set_size(3 * PAGE_SIZE).unwrap();
+        let mut buf = [0xffu8; 1];
+        vmo.read(2 * PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [0]);
+    }
+
+    #[test]
+    fn op_range() {
+        let vmo = Vmo::new(4 * PAGE_SIZE).unwrap();
+        vmo.op_range(VmoOpType::Commit, 1, PAGE_SIZE).unwrap();
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        vmo.op_range(VmoOpType::Decommit, 0, PAGE_SIZE).unwrap();
+        assert_eq!(vmo.committed_bytes(), PAGE_SIZE);
+
+        vmo.write(2 * PAGE_SIZE, &[7u8; PAGE_SIZE]).unwrap();
+        vmo.op_range(VmoOpType::Zero, 2 * PAGE_SIZE + 1, 2).unwrap();
+        let mut buf = [0u8; 4];
+        vmo.read(2 * PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [7, 0, 0, 7]);
+
+        // 整页清零会释放页
+        vmo.op_range(VmoOpType::Zero, 2 * PAGE_SIZE, PAGE_SIZE).unwrap();
+        assert_eq!(vmo.committed_bytes(), PAGE_SIZE);
+        assert_eq!(
+            vmo.op_range(VmoOpType::Commit, 0, 5 * PAGE_SIZE).err(),
+            Some(ZxError::OUT_OF_RANGE)
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let parent = Vmo::new(2 * PAGE_SIZE).unwrap();
+        parent.write(0, &[1, 2]).unwrap();
+        let child = parent
+            .create_child(VmoChildType::Snapshot, 0, 3 * PAGE_SIZE)
+            .unwrap();
+        assert_eq!(child.size(), 3 * PAGE_SIZE);
+        // 页帧是共享的
+        assert_eq!(child.committed_bytes(), PAGE_SIZE);
+        assert_eq!(parent.private_bytes(), 0);
+        assert_eq!(child.private_bytes(), 0);
+
+        let mut buf = [0u8; 2];
+        child.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+
+        // 子对象写入时复制，父对象看不到
+        child.write(0, &[3]).unwrap();
+        parent.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+        child.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [3, 2]);
+        assert_eq!(parent.private_bytes(), PAGE_SIZE);
+        assert_eq!(child.private_bytes(), PAGE_SIZE);
+
+        // 父对象写入时复制，子对象看不到
+        let child2 = parent
+            .create_child(VmoChildType::SnapshotAtLeastOnWrite, 0, PAGE_SIZE)
+            .unwrap();
+        parent.write(1, &[4]).unwrap();
+        child2.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+
+        assert_eq!(
+            parent.create_child(VmoChildType::Snapshot, 1, PAGE_SIZE).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+    }
+
+    #[test]
+    fn snapshot_reclaim() {
+        let parent = Vmo::new(PAGE_SIZE).unwrap();
+        parent.write(0, &[1]).unwrap();
+        let child = parent
+            .create_child(VmoChildType::Snapshot, 0, PAGE_SIZE)
+            .unwrap();
+        assert_eq!(parent.private_bytes(), 0);
+        // 子对象销毁后，共享的页帧重新归父对象独占
+        drop(child);
+        assert_eq!(parent.private_bytes(), PAGE_SIZE);
+    }
+
+    #[test]
+    fn slice() {
+        let parent = Vmo::new(4 * PAGE_SIZE).unwrap();
+        let slice = parent
+            .create_child(VmoChildType::Slice, PAGE_SIZE, 2 * PAGE_SIZE)
+            .unwrap();
+        // 双方的写入互相可见
+        slice.write(0, &[1]).unwrap();
+        parent.write(PAGE_SIZE + 1, &[2]).unwrap();
+        let mut buf = [0u8; 2];
+        parent.read(PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+        slice.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(slice.committed_bytes(), 0);
+
+        // 切片的切片
+        let slice2 = slice
+            .create_child(VmoChildType::Slice, PAGE_SIZE, PAGE_SIZE)
+            .unwrap();
+        slice2.write(0, &[5]).unwrap();
+        parent.read(2 * PAGE_SIZE, &mut buf[..1]).unwrap();
+        assert_eq!(buf[0], 5);
+
+        // 切片的快照
+        let snapshot = slice
+            .create_child(VmoChildType::Snapshot, 0, 2 * PAGE_SIZE)
+            .unwrap();
+        slice.write(0, &[9]).unwrap();
+        snapshot.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+
+        assert_eq!(slice.write(2 * PAGE_SIZE, &[0]).err(), Some(ZxError::OUT_OF_RANGE));
+        assert_eq!(slice.set_size(PAGE_SIZE).err(), Some(ZxError::NOT_SUPPORTED));
+        assert_eq!(
+            parent.create_child(VmoChildType::Slice, 3 * PAGE_SIZE, 2 * PAGE_SIZE).err(),
+            Some(ZxError::OUT_OF_RANGE)
+        );
+    }
+}